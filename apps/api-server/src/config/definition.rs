@@ -8,6 +8,11 @@ pub struct ServerConfig {
     pub port: u16,
     pub shutdown_timeout: u64,
     pub cors: bool,
+    /// Take the actor and tenant from the `x-user-id` / `x-tenant-id`
+    /// headers. Enable only behind a proxy that authenticates callers and
+    /// overwrites these headers; anyone else could forge them.
+    #[serde(default)]
+    pub trust_identity_headers: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
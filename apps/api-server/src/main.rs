@@ -7,7 +7,13 @@ use anyhow::Result;
 use opentelemetry::trace::TracerProvider;
 use ro_adapters::database::postgres::user_repo::PUserRepository;
 use ro_core::services::user_service::UserService;
-use ro_messaging::nats::{NatsClient, middleware::tracing_middleware as nats_tracing_mw};
+use ro_messaging::{
+    Publisher,
+    nats::{
+        NatsClient,
+        middleware::{metrics_middleware, tracing_middleware as nats_tracing_mw},
+    },
+};
 use std::sync::Arc;
 use tower_http::cors::{Any, CorsLayer};
 use tracing_subscriber::{
@@ -61,18 +67,26 @@ async fn main() -> Result<()> {
     // 1. Create Adapter (Repository)
     let user_repo = PUserRepository::new(Arc::clone(&db));
 
-    let nats = NatsClient::connect(
-        cfg.shared.common.name.clone(),
-        cfg.shared.nats.clone(),
-        vec![nats_tracing_mw()],
-    )
-    .await?;
-
     // 2. Create Service (Inject Repository)
-    let user_service = UserService::new(Arc::new(user_repo), Arc::new(nats));
+    // Events are written to the outbox and published by the worker's relay.
+    let user_service = UserService::new(Arc::new(user_repo));
+
+    // NATS is only needed for readiness today; skip it when disabled.
+    let nats = if cfg.shared.nats.enabled {
+        Some(
+            NatsClient::connect(
+                cfg.shared.common.name.clone(),
+                cfg.shared.nats.clone(),
+                vec![nats_tracing_mw(), metrics_middleware()],
+            )
+            .await?,
+        )
+    } else {
+        None
+    };
 
     // 3. Create State (Inject Service)
-    let state = Arc::new(states::AppState::new(user_service));
+    let state = Arc::new(states::AppState::new(user_service, nats.clone()));
 
     let cors: CorsLayer = CorsLayer::new()
         .allow_origin(Any)
@@ -80,6 +94,10 @@ async fn main() -> Result<()> {
         .allow_methods(Any);
 
     let app = routes::create_router(state)
+        .layer(axum::middleware::from_fn_with_state(
+            cfg.server.trust_identity_headers,
+            middlewares::context::request_context_middleware,
+        ))
         .layer(axum::middleware::from_fn(
             middlewares::metrics::metric_middleware,
         ))
@@ -100,6 +118,10 @@ async fn main() -> Result<()> {
         .with_graceful_shutdown(shutdown_signal())
         .await?;
 
+    if let Some(nats) = nats {
+        nats.close().await?;
+    }
+
     tracing::info!("Server shut down gracefully");
 
     Ok(())
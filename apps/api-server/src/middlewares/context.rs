@@ -0,0 +1,48 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+
+use ro_common::context::RequestContext;
+
+use crate::middlewares::RequestId;
+
+/// Authenticated user, set by the trusted gateway in front of the API.
+const USER_ID_HEADER: &str = "x-user-id";
+const TENANT_ID_HEADER: &str = "x-tenant-id";
+
+/// Middleware to run the request inside a `RequestContext`
+///
+/// Must run after `request_id_middleware`. Messages published and rows
+/// written while handling the request carry its actor, request and tenant.
+///
+/// The identity headers are honoured only when `trust_identity_headers`
+/// (`ServerConfig::trust_identity_headers`) is set; otherwise the request
+/// runs as the system actor with no tenant.
+pub async fn request_context_middleware(
+    State(trust_identity_headers): State<bool>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let ctx = {
+        let header = |name: &str| {
+            if !trust_identity_headers {
+                return None;
+            }
+            request
+                .headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .filter(|v| !v.is_empty())
+                .map(|v| v.to_string())
+        };
+        RequestContext {
+            actor_id: header(USER_ID_HEADER),
+            request_id: request.extensions().get::<RequestId>().map(|v| v.0.clone()),
+            tenant_id: header(TENANT_ID_HEADER),
+        }
+    };
+
+    ctx.scope(next.run(request)).await
+}
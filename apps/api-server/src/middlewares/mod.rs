@@ -1,3 +1,4 @@
+pub mod context;
 pub mod metrics;
 pub mod request_id;
 pub mod tracing;
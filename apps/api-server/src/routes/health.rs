@@ -1,12 +1,16 @@
 use axum::{Json, extract::State, http::StatusCode};
 use serde::Serialize;
 
+use ro_messaging::nats::ConnectionStatus;
+
 use crate::states::SharedState;
 
 #[derive(Debug, Serialize)]
 pub struct HealthResponse {
     pub status: String,
     pub version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nats: Option<ConnectionStatus>,
 }
 
 #[tracing::instrument(name = "liveness")]
@@ -14,12 +18,22 @@ pub async fn liveness() -> StatusCode {
     StatusCode::OK
 }
 
+/// Ready while the NATS connection (if enabled) is up.
 #[tracing::instrument(name = "readiness")]
-pub async fn readiness(State(_state): State<SharedState>) -> Json<HealthResponse> {
+pub async fn readiness(State(state): State<SharedState>) -> (StatusCode, Json<HealthResponse>) {
+    let nats = state.nats.as_ref().map(|nats| nats.status());
+    let healthy = nats.as_ref().is_none_or(ConnectionStatus::is_healthy);
+
     let response = HealthResponse {
-        status: "healthy".to_string(),
+        status: if healthy { "healthy" } else { "unhealthy" }.to_string(),
         version: env!("CARGO_PKG_VERSION").to_string(),
+        nats,
     };
 
-    Json(response)
+    let code = if healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (code, Json(response))
 }
@@ -1,15 +1,18 @@
 use std::sync::Arc;
 
 use ro_core::services::user_service::UserService;
+use ro_messaging::nats::NatsClient;
 
 #[derive(Debug, Clone)]
 pub struct AppState {
     pub user_service: UserService,
+    /// `None` when NATS is disabled in config.
+    pub nats: Option<NatsClient>,
 }
 
 impl AppState {
-    pub fn new(user_service: UserService) -> Self {
-        Self { user_service }
+    pub fn new(user_service: UserService, nats: Option<NatsClient>) -> Self {
+        Self { user_service, nats }
     }
 }
 
@@ -2,8 +2,26 @@ use serde::{Deserialize, Serialize};
 
 use ro_config::config::SharedConfig;
 
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct HealthConfig {
+    pub host: String,
+    pub port: u16,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            host: "0.0.0.0".to_string(),
+            port: 3002,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct WorkerConfig {
     #[serde(flatten)]
     pub shared: SharedConfig,
+    /// Liveness / readiness endpoint.
+    #[serde(default)]
+    pub health: HealthConfig,
 }
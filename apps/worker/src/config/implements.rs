@@ -5,6 +5,10 @@ use crate::config::definition::WorkerConfig;
 use ro_config as config;
 
 impl WorkerConfig {
+    pub fn health_addr(&self) -> String {
+        format!("{}:{}", self.health.host, self.health.port)
+    }
+
     pub fn get_config() -> &'static WorkerConfig {
         static CONFIG: OnceLock<WorkerConfig> = OnceLock::new();
         CONFIG.get_or_init(|| {
@@ -0,0 +1,41 @@
+use axum::{Json, Router, extract::State, http::StatusCode, routing::get};
+use serde::Serialize;
+
+use ro_messaging::nats::{ConnectionStatus, NatsClient};
+
+#[derive(Debug, Serialize)]
+pub struct HealthResponse {
+    pub status: String,
+    pub version: String,
+    pub nats: ConnectionStatus,
+}
+
+pub fn router(nats: NatsClient) -> Router {
+    Router::new()
+        .route("/health/liveness", get(liveness))
+        .route("/health/readiness", get(readiness))
+        .with_state(nats)
+}
+
+async fn liveness() -> StatusCode {
+    StatusCode::OK
+}
+
+/// Ready while the NATS connection is up.
+async fn readiness(State(nats): State<NatsClient>) -> (StatusCode, Json<HealthResponse>) {
+    let status = nats.status();
+    let healthy = status.is_healthy();
+
+    let response = HealthResponse {
+        status: if healthy { "healthy" } else { "unhealthy" }.to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        nats: status,
+    };
+
+    let code = if healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (code, Json(response))
+}
@@ -1,9 +1,21 @@
 mod config;
+mod health;
 
-use ro_core::domain::entities::user::User;
+use std::sync::Arc;
+
+use ro_adapters::messaging::{idempotency::PgIdempotencyStore, outbox_relay::OutboxRelay};
+use ro_common::id::generate_nanoid;
+use ro_core::domain::{events::UserCreated, topics};
+use ro_db::orm;
 use ro_messaging::{
-    QueueSubscriber, handler,
-    nats::{NatsClient, middleware::tracing_middleware},
+    BucketOptions, DurableSubscriberExt, KeyValueStore, LeaderElector, MessagingError,
+    PartitionedConsumer, QueueSubscriberExt, SubscribeOptions,
+    nats::{
+        NatsClient,
+        middleware::{
+            idempotency_middleware, metrics_middleware, retry_middleware, tracing_middleware,
+        },
+    },
 };
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
@@ -24,21 +36,103 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let nats = NatsClient::connect(
         cfg.shared.common.name.clone(),
         cfg.shared.nats.clone(),
-        vec![tracing_middleware()],
+        vec![tracing_middleware(), metrics_middleware()],
     )
     .await?;
 
-    nats.queue_subscribe(
-        "user.created",
-        "worker-group",
-        handler(|msg| async move {
-            let user = msg.json::<User>()?;
-            tracing::info!(username = %user.username, id = %user.id, "user.created received");
-            // inject services here
-            Ok(())
+    let db = orm::new_db(cfg.shared.database.clone()).await?;
+
+    // Duplicates are skipped before retries, so a dead-lettered message
+    // also counts as handled.
+    let mut consumer_middlewares = Vec::new();
+    let mut inbox = None;
+    if let Some(idempotency) = &cfg.shared.nats.idempotency {
+        let store = Arc::new(PgIdempotencyStore::new(Arc::clone(&db)));
+        consumer_middlewares.push(idempotency_middleware(
+            store.clone(),
+            cfg.shared.common.name.clone(),
+            idempotency.ttl(),
+            idempotency.lease(),
+        ));
+        inbox = Some((store, idempotency.purge_interval()));
+    }
+    // Failed handlers are retried, then dead-lettered through the same
+    // connection; with JetStream the dead letter is stored (PubAck awaited)
+    // before the original is acked.
+    if let Some(policy) = cfg.shared.nats.retry.clone() {
+        let dead_letter = match &cfg.shared.nats.jetstream {
+            Some(_) => nats.persistent(),
+            None => nats.clone(),
+        };
+        consumer_middlewares.push(retry_middleware(policy, Some(Arc::new(dead_letter))));
+    }
+    let nats = nats.with_middlewares(consumer_middlewares);
+    // Keyed events go to `user.created.<n>`, which only the partitioned
+    // (JetStream) consumer below reads; a plain subscription would miss them.
+    if cfg.shared.nats.partitioning.is_some() && cfg.shared.nats.jetstream.is_none() {
+        return Err("nats.partitioning requires nats.jetstream".into());
+    }
+
+    // Identifies this replica in leader election and partition assignment.
+    let instance = format!("{}-{}", cfg.shared.common.name, generate_nanoid());
+
+    // Publish events written to the outbox by the api-server; with leader
+    // election only the elected replica relays. Rows are marked sent only
+    // once JetStream acknowledged them.
+    let publisher = match &cfg.shared.nats.jetstream {
+        Some(_) => nats.persistent(),
+        None => nats.clone(),
+    };
+    let relay = OutboxRelay::new(db, Arc::new(publisher), cfg.shared.outbox.clone());
+    let (leader, relay_task) = match &cfg.shared.nats.leader_election {
+        Some(election) => {
+            let bucket = BucketOptions::new(&election.bucket).with_max_age(election.ttl());
+            let kv: Arc<dyn KeyValueStore> = Arc::new(nats.key_value(&bucket).await?);
+            let leader =
+                LeaderElector::new(kv, "outbox-relay", instance.clone(), election.ttl()).start();
+            let relay_task = leader.run_while_leader(move || relay.clone().run());
+            (Some(leader), relay_task)
+        }
+        None => (None, tokio::spawn(relay.run())),
+    };
+    // Expired idempotency claims are purged by the leader as well.
+    let purge_task = inbox.map(|(store, every)| match &leader {
+        Some(leader) => leader.run_while_leader(move || {
+            let store = Arc::clone(&store);
+            async move { store.run_purge(every).await }
         }),
-    )
-    .await?;
+        None => tokio::spawn(async move { store.run_purge(every).await }),
+    });
+
+    // JetStream keeps events published while the worker is down;
+    // fall back to a plain queue group when it isn't configured. With
+    // partitioning, events of one user are handled in order and the
+    // partitions are spread over the replicas.
+    let mut partitions = None;
+    match (&cfg.shared.nats.jetstream, &cfg.shared.nats.partitioning) {
+        (Some(_), Some(partitioning)) => {
+            let bucket = BucketOptions::new(&partitioning.bucket).with_max_age(partitioning.ttl());
+            let kv: Arc<dyn KeyValueStore> = Arc::new(nats.key_value(&bucket).await?);
+            let ordered = SubscribeOptions::from_config(&cfg.shared.nats).ordered();
+            let consumer = PartitionedConsumer::new(
+                Arc::new(nats.with_subscribe_options(ordered)),
+                kv,
+                topics::USER_CREATED.name(),
+                "worker",
+                instance.clone(),
+                partitioning,
+            )?;
+            partitions = Some(consumer.start(topics::USER_CREATED.handler(on_user_created)));
+        }
+        (Some(_), None) => {
+            nats.durable_subscribe_to(&topics::USER_CREATED, "worker", on_user_created)
+                .await?;
+        }
+        (None, _) => {
+            nats.queue_subscribe_to(&topics::USER_CREATED, "worker-group", on_user_created)
+                .await?;
+        }
+    }
 
     // // 2. Connect to NATS
     // // (Assumes you add nats_url to your config, hardcoded for demo)
@@ -60,7 +154,37 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     //         // email_service.send_welcome(user).await;
     //     }
     // }
+    let listener = tokio::net::TcpListener::bind(cfg.health_addr()).await?;
+    tracing::info!("Health endpoint listening on {}", cfg.health_addr());
+    let health_router = health::router(nats.clone());
+    let health_task = tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, health_router).await {
+            tracing::error!(error = %e, "health endpoint failed");
+        }
+    });
+
     tokio::signal::ctrl_c().await?;
-    nats.close().await?;
+    tracing::info!("Worker shutting down...");
+    // Hand the relay over to another replica right away.
+    if let Some(leader) = leader {
+        leader.resign().await;
+    }
+    relay_task.abort();
+    if let Some(purge_task) = purge_task {
+        purge_task.abort();
+    }
+    // Hand the partitions over, letting their running handlers finish.
+    if let Some(partitions) = partitions {
+        partitions.stop().await;
+    }
+    // Let in-flight handlers finish (and ack) before the connection closes.
+    nats.drain(cfg.shared.nats.drain_timeout()).await?;
+    health_task.abort();
+    Ok(())
+}
+
+async fn on_user_created(user: UserCreated) -> Result<(), MessagingError> {
+    tracing::info!(username = %user.username, id = %user.id, "user.created received");
+    // inject services here
     Ok(())
 }
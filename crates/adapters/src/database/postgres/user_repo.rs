@@ -1,14 +1,16 @@
 use std::{fmt::Debug, sync::Arc};
 
 use async_trait::async_trait;
+use ro_common::context::RequestContext;
 use ro_core::domain::{
     entities::user::User as DomainUser,
     ports::user_repo::{UserError, UserRepository},
 };
-use ro_db::orm::repo::Repository;
-use sea_orm::{ConnectionTrait, EntityTrait};
+use ro_db::orm::{context::DbContext, outbox::OutboxEvent, repo::Repository};
+use ro_messaging::Message;
+use sea_orm::{ConnectionTrait, EntityTrait, TransactionTrait};
 
-use crate::database::entities::user::{self, ActiveModel as UserActiveModel, Entity as UserEntity};
+use crate::database::entities::user::{ActiveModel as UserActiveModel, Entity as UserEntity};
 
 #[derive(Debug, Clone)]
 pub struct PUserRepository<C>
@@ -32,7 +34,7 @@ where
 #[async_trait]
 impl<C> UserRepository for PUserRepository<C>
 where
-    C: ConnectionTrait + Send + Sync + Debug + 'static,
+    C: ConnectionTrait + TransactionTrait + Send + Sync + Debug + 'static,
 {
     async fn find_by_id(&self, id: &str) -> Result<Option<DomainUser>, UserError> {
         // 1. Fetch from DB using SeaORM
@@ -48,23 +50,28 @@ where
         }
     }
 
-    async fn save(&self, user: &DomainUser) -> Result<(), UserError> {
+    async fn save(&self, user: &DomainUser, events: Vec<Message>) -> Result<(), UserError> {
         // 1. Map Domain Entity -> SeaORM ActiveModel
-        // let user_model = UserActiveModel {
-        //     id: Set(user.id.clone()),
-        //     username: Set(user.username.clone()),
-        //     email: Set(user.email.clone()),
-        //     active: Set(user.active),
-        // };
-        let user = user.clone();
-        let user_model: UserActiveModel = user.into();
+        let user_model: UserActiveModel = user.clone().into();
 
-        // 2. Execute Insert/Update (Upsert equivalent)
+        // 2. Map outgoing events -> outbox rows; the relay publishes them
+        //    later, so capture the request context now
+        let ctx = RequestContext::current();
+        let events = events
+            .into_iter()
+            .map(|msg| msg.with_context(&ctx))
+            .map(|msg| OutboxEvent {
+                topic: msg.topic,
+                payload: msg.data.to_vec(),
+                headers: msg.attrs,
+                available_at: None,
+            })
+            .collect();
+
+        // 3. Insert user + outbox rows in one transaction
         // Note: SeaORM 'insert' will fail on duplicate.
-        // For upsert, you typically check existence or use `on_conflict` if supported.
-        // Here is a simple Insert for demonstration:
-        user::Entity::insert(user_model)
-            .exec(self.repo.db.as_ref())
+        self.repo
+            .create_with_outbox(&DbContext::current(), user_model, events)
             .await
             .map_err(|e| UserError::System(e.to_string()))?;
 
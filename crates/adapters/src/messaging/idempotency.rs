@@ -0,0 +1,74 @@
+use std::{fmt::Debug, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use ro_db::orm::inbox;
+use ro_messaging::{Claim, IdempotencyStore, MessagingError, error::IoOp};
+use sea_orm::ConnectionTrait;
+
+/// `IdempotencyStore` backed by the Postgres `inbox` table, shared by every
+/// consumer instance.
+#[derive(Debug, Clone)]
+pub struct PgIdempotencyStore<C>
+where
+    C: ConnectionTrait + Send + Sync + Debug,
+{
+    db: Arc<C>,
+}
+
+impl<C> PgIdempotencyStore<C>
+where
+    C: ConnectionTrait + Send + Sync + Debug,
+{
+    pub fn new(db: Arc<C>) -> Self {
+        Self { db }
+    }
+
+    /// Delete expired claims; run periodically to keep the table small.
+    pub async fn purge_expired(&self) -> Result<u64, MessagingError> {
+        inbox::purge_expired(self.db.as_ref())
+            .await
+            .map_err(|e| MessagingError::io(IoOp::Store, e))
+    }
+
+    /// Purge expired claims every `interval`, forever.
+    pub async fn run_purge(&self, interval: Duration) {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            match self.purge_expired().await {
+                Ok(0) => {}
+                Ok(n) => tracing::debug!(purged = n, "Idempotency: expired claims purged"),
+                Err(e) => tracing::error!(error = %e, "Idempotency: purge failed"),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<C> IdempotencyStore for PgIdempotencyStore<C>
+where
+    C: ConnectionTrait + Send + Sync + Debug,
+{
+    async fn try_claim(&self, key: &str, lease: Duration) -> Result<Claim, MessagingError> {
+        let held = inbox::try_claim(self.db.as_ref(), key, lease)
+            .await
+            .map_err(|e| MessagingError::io(IoOp::Store, e))?;
+        Ok(match held {
+            None => Claim::Acquired,
+            Some(claim) if claim.completed_at.is_some() => Claim::Done,
+            Some(_) => Claim::InProgress,
+        })
+    }
+
+    async fn complete(&self, key: &str, ttl: Duration) -> Result<(), MessagingError> {
+        inbox::complete(self.db.as_ref(), key, ttl)
+            .await
+            .map_err(|e| MessagingError::io(IoOp::Store, e))
+    }
+
+    async fn release(&self, key: &str) -> Result<(), MessagingError> {
+        inbox::release(self.db.as_ref(), key)
+            .await
+            .map_err(|e| MessagingError::io(IoOp::Store, e))
+    }
+}
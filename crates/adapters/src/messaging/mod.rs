@@ -0,0 +1,3 @@
+pub mod idempotency;
+pub mod outbox_relay;
+pub mod outbox_scheduler;
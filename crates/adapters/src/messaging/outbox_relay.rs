@@ -0,0 +1,117 @@
+use std::{fmt::Debug, sync::Arc};
+
+use bytes::Bytes;
+use chrono::{TimeDelta, Utc};
+use ro_config::config::outbox::OutboxConfig;
+use ro_db::orm::outbox;
+use ro_messaging::{Publisher, nats::factory::HEADER_MESSAGE_ID};
+use sea_orm::{ConnectionTrait, DbErr, TransactionTrait};
+
+/// Publishes pending `outbox` rows through any `Publisher`.
+///
+/// Each batch runs in its own transaction with the rows locked
+/// (`SKIP LOCKED`), so several relays can run side by side. Failed publishes
+/// are retried with exponential backoff until `max_attempts`, after which
+/// the row is marked `failed`.
+///
+/// A row is marked sent as soon as `publish` returns, so the publisher must
+/// only return once the message is stored: with NATS, pass a
+/// `NatsClient::persistent` handle, which awaits the JetStream PubAck.
+#[derive(Debug)]
+pub struct OutboxRelay<C>
+where
+    C: ConnectionTrait + TransactionTrait + Send + Sync + Debug,
+{
+    db: Arc<C>,
+    publisher: Arc<dyn Publisher>,
+    cfg: OutboxConfig,
+}
+
+// Manual impl: the connection is shared through the `Arc`, so `C` itself
+// need not be `Clone`.
+impl<C> Clone for OutboxRelay<C>
+where
+    C: ConnectionTrait + TransactionTrait + Send + Sync + Debug,
+{
+    fn clone(&self) -> Self {
+        Self {
+            db: Arc::clone(&self.db),
+            publisher: Arc::clone(&self.publisher),
+            cfg: self.cfg.clone(),
+        }
+    }
+}
+
+impl<C> OutboxRelay<C>
+where
+    C: ConnectionTrait + TransactionTrait + Send + Sync + Debug + 'static,
+{
+    pub fn new(db: Arc<C>, publisher: Arc<dyn Publisher>, cfg: OutboxConfig) -> Self {
+        Self { db, publisher, cfg }
+    }
+
+    /// Poll forever; abort the task to stop.
+    ///
+    /// An interrupted batch is rolled back and its rows published again on
+    /// the next run (at-least-once).
+    pub async fn run(self) {
+        tracing::info!("Outbox: relay started");
+        let mut interval = tokio::time::interval(self.cfg.poll_interval());
+        loop {
+            interval.tick().await;
+            // Keep draining while batches come back full.
+            loop {
+                match self.relay_batch().await {
+                    Ok(n) if n as u64 >= self.cfg.batch_size => continue,
+                    Ok(_) => break,
+                    Err(e) => {
+                        tracing::error!(error = %e, "Outbox: relay batch failed");
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Publish one batch of due rows; returns how many rows were handled.
+    pub async fn relay_batch(&self) -> Result<usize, DbErr> {
+        let txn = self.db.begin().await?;
+        let rows = outbox::fetch_due(&txn, self.cfg.batch_size).await?;
+        let count = rows.len();
+
+        for row in rows {
+            // The row id doubles as message id, so a re-published row is
+            // recognised as a duplicate downstream.
+            let mut headers = row.header_map();
+            headers
+                .entry(HEADER_MESSAGE_ID.to_string())
+                .or_insert_with(|| row.id.clone());
+            let payload = Bytes::from(row.payload.clone());
+
+            match self.publisher.publish(&row.topic, payload, headers).await {
+                Ok(()) => {
+                    outbox::mark_sent(&txn, row).await?;
+                }
+                Err(e) => {
+                    let attempt = row.attempts + 1;
+                    let retry_at = (attempt < self.cfg.max_attempts).then(|| {
+                        Utc::now()
+                            + TimeDelta::from_std(self.cfg.backoff(attempt)).unwrap_or_default()
+                    });
+                    tracing::warn!(
+                        id = %row.id,
+                        topic = %row.topic,
+                        attempt,
+                        gave_up = retry_at.is_none(),
+                        error = %e,
+                        "Outbox: publish failed"
+                    );
+                    outbox::mark_failed(&txn, row, e.to_string(), retry_at).await?;
+                }
+            }
+        }
+
+        txn.commit().await?;
+        Ok(count)
+    }
+}
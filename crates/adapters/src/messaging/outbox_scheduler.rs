@@ -0,0 +1,116 @@
+use std::{collections::HashMap, fmt::Debug, sync::Arc};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use ro_common::context::RequestContext;
+use ro_db::orm::{
+    context::DbContext,
+    outbox::{self, OutboxEvent},
+    repo::Repository,
+};
+use ro_messaging::{
+    Message, MessagingError, Publisher, ScheduledPublisher, error::IoOp,
+    nats::factory::HEADER_MESSAGE_ID,
+};
+use sea_orm::{ActiveValue::Set, ConnectionTrait};
+
+/// Durable `ScheduledPublisher` over the Postgres `outbox` table.
+///
+/// Messages are written as outbox rows with `available_at` set to the due
+/// time and published by the `OutboxRelay`, so they survive restarts and
+/// are dispatched by one replica only. They go out within one relay
+/// `poll_interval` of being due. `publish` writes a row that is due at once.
+///
+/// The schedule id is the row id, which the relay uses as message id; a
+/// `Nats-Msg-Id` in `attrs` is used as row id instead. The caller's
+/// `RequestContext` is stored with the row, as for events saved by the
+/// repositories.
+#[derive(Debug, Clone)]
+pub struct OutboxScheduler<C>
+where
+    C: ConnectionTrait + Send + Sync + Debug,
+{
+    repo: Repository<C>,
+}
+
+impl<C> OutboxScheduler<C>
+where
+    C: ConnectionTrait + Send + Sync + Debug,
+{
+    pub fn new(db: Arc<C>) -> Self {
+        Self {
+            repo: Repository::new(db),
+        }
+    }
+
+    /// Outbox row for a message; the relay publishes it later, so capture
+    /// the request context now.
+    fn event(topic: &str, data: Bytes, attrs: HashMap<String, String>) -> OutboxEvent {
+        let mut msg = Message::new(topic, data);
+        msg.attrs = attrs;
+        let msg = msg.with_context(&RequestContext::current());
+        OutboxEvent {
+            headers: msg.attrs,
+            ..OutboxEvent::new(msg.topic, msg.data.to_vec())
+        }
+    }
+
+    async fn insert(&self, event: OutboxEvent) -> Result<String, MessagingError> {
+        let id = event.headers.get(HEADER_MESSAGE_ID).cloned();
+        let mut row: outbox::ActiveModel = event.into();
+        if let Some(id) = id {
+            row.id = Set(id);
+        }
+        let model = self
+            .repo
+            .create(&DbContext::current(), row)
+            .await
+            .map_err(|e| MessagingError::io(IoOp::Store, e))?;
+        Ok(model.id)
+    }
+}
+
+#[async_trait]
+impl<C> Publisher for OutboxScheduler<C>
+where
+    C: ConnectionTrait + Send + Sync + Debug,
+{
+    async fn publish(
+        &self,
+        topic: &str,
+        data: Bytes,
+        attrs: HashMap<String, String>,
+    ) -> Result<(), MessagingError> {
+        self.insert(Self::event(topic, data, attrs))
+            .await
+            .map(|_| ())
+    }
+
+    async fn close(&self) -> Result<(), MessagingError> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<C> ScheduledPublisher for OutboxScheduler<C>
+where
+    C: ConnectionTrait + Send + Sync + Debug,
+{
+    async fn publish_at(
+        &self,
+        topic: &str,
+        data: Bytes,
+        attrs: HashMap<String, String>,
+        at: DateTime<Utc>,
+    ) -> Result<String, MessagingError> {
+        let event = Self::event(topic, data, attrs).with_available_at(at);
+        self.insert(event).await
+    }
+
+    async fn cancel(&self, id: &str) -> Result<bool, MessagingError> {
+        outbox::cancel(self.repo.db.as_ref(), id)
+            .await
+            .map_err(|e| MessagingError::io(IoOp::Store, e))
+    }
+}
@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+use crate::domain::entities::user::User;
+
+/// Published when a user registers.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UserCreated {
+    pub id: String,
+    pub username: String,
+    pub email: String,
+}
+
+impl From<&User> for UserCreated {
+    fn from(user: &User) -> Self {
+        Self {
+            id: user.id.clone(),
+            username: user.username.clone(),
+            email: user.email.clone(),
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use async_trait::async_trait;
 use core::fmt::Debug;
+use ro_messaging::Message;
 use thiserror::Error;
 
 use crate::domain::entities::user::User;
@@ -15,5 +16,6 @@ pub enum UserError {
 #[async_trait]
 pub trait UserRepository: Send + Sync + 'static + Debug {
     async fn find_by_id(&self, id: &str) -> Result<Option<User>, UserError>;
-    async fn save(&self, user: &User) -> Result<(), UserError>;
+    /// Persist `user` and queue `events` for publishing, atomically.
+    async fn save(&self, user: &User, events: Vec<Message>) -> Result<(), UserError>;
 }
@@ -0,0 +1,10 @@
+//! Every topic the domain publishes, with its payload type.
+//!
+//! Publishers and subscribers both use these constants, so a topic name
+//! is spelled once and a payload mismatch is a compile error.
+
+use ro_messaging::Topic;
+
+use crate::domain::events::UserCreated;
+
+pub const USER_CREATED: Topic<UserCreated> = Topic::new("user.created");
@@ -2,10 +2,12 @@ pub mod domain {
     pub mod entities {
         pub mod user;
     }
+    pub mod events;
     pub mod ports {
         pub mod messaging;
         pub mod user_repo;
     }
+    pub mod topics;
 }
 pub mod services {
     pub mod user_service;
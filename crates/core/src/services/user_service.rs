@@ -1,21 +1,22 @@
 use crate::domain::{
     entities::user::User,
+    events::UserCreated,
     ports::user_repo::{UserError, UserRepository},
+    topics,
 };
 use ro_common::id::generate_nanoid;
-use ro_messaging::{Publisher, traits::PublisherExt};
+use ro_messaging::Encoding;
 use std::sync::Arc; // Reusing your shared lib
 
 #[derive(Debug, Clone)]
 pub struct UserService {
     // The service owns the Abstract Repository (Port), not the Concrete Adapter.
     repo: Arc<dyn UserRepository>,
-    publisher: Arc<dyn Publisher>,
 }
 
 impl UserService {
-    pub fn new(repo: Arc<dyn UserRepository>, publisher: Arc<dyn Publisher>) -> Self {
-        Self { repo, publisher }
+    pub fn new(repo: Arc<dyn UserRepository>) -> Self {
+        Self { repo }
     }
 
     pub async fn register_user(&self, username: String, email: String) -> Result<User, UserError> {
@@ -27,13 +28,14 @@ impl UserService {
         let new_user = User::new(id, username, email);
 
         // 3. Persistence: Call the Port
-        self.repo.save(&new_user).await?;
-
-        let _ = self
-            .publisher
-            .publish_json("user.created", &new_user)
-            .await
-            .map_err(|e| UserError::System(e.to_string()));
+        // The event goes to the outbox in the same transaction and is
+        // published by the relay, so the DB and the event stream agree.
+        // Keyed by user so a user's events are handled in order.
+        let event = topics::USER_CREATED
+            .message(&UserCreated::from(&new_user), Encoding::Json)
+            .map_err(|e| UserError::System(e.to_string()))?
+            .with_partition_key(&new_user.id);
+        self.repo.save(&new_user, vec![event]).await?;
 
         Ok(new_user)
     }
@@ -0,0 +1,58 @@
+use std::future::Future;
+
+/// Actor recorded when no request context is in scope.
+pub const SYSTEM_ACTOR: &str = "system";
+
+/// Who and what a unit of work is running on behalf of.
+///
+/// Set per HTTP request by the api-server and per message by subscribers,
+/// then read wherever the actor is needed (message headers, `DbContext`)
+/// without threading it through every call.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequestContext {
+    pub actor_id: Option<String>,
+    pub request_id: Option<String>,
+    pub tenant_id: Option<String>,
+}
+
+tokio::task_local! {
+    static CURRENT: RequestContext;
+}
+
+impl RequestContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_actor(mut self, actor_id: impl Into<String>) -> Self {
+        self.actor_id = Some(actor_id.into());
+        self
+    }
+
+    pub fn with_request_id(mut self, request_id: impl Into<String>) -> Self {
+        self.request_id = Some(request_id.into());
+        self
+    }
+
+    pub fn with_tenant(mut self, tenant_id: impl Into<String>) -> Self {
+        self.tenant_id = Some(tenant_id.into());
+        self
+    }
+
+    /// The actor, or `"system"` when none is set.
+    pub fn actor_or_system(&self) -> &str {
+        self.actor_id.as_deref().unwrap_or(SYSTEM_ACTOR)
+    }
+
+    /// Run `fut` with `self` as the current context.
+    ///
+    /// Spawned tasks do not inherit it; scope them again if needed.
+    pub async fn scope<F: Future>(self, fut: F) -> F::Output {
+        CURRENT.scope(self, fut).await
+    }
+
+    /// The context of the running task, or an empty one outside `scope`.
+    pub fn current() -> Self {
+        CURRENT.try_with(Clone::clone).unwrap_or_default()
+    }
+}
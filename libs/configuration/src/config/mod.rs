@@ -2,10 +2,14 @@ pub mod db;
 pub mod log;
 pub mod nats;
 pub mod otel;
+pub mod outbox;
 
 use serde::{Deserialize, Serialize};
 
-use crate::config::{db::DatabaseConfig, log::LoggingConfig, nats::NatsConfig, otel::OtelConfig};
+use crate::config::{
+    db::DatabaseConfig, log::LoggingConfig, nats::NatsConfig, otel::OtelConfig,
+    outbox::OutboxConfig,
+};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CommonConfig {
@@ -22,4 +26,6 @@ pub struct SharedConfig {
     pub logging: LoggingConfig,
     pub nats: NatsConfig,
     pub otel: OtelConfig,
+    #[serde(default)]
+    pub outbox: OutboxConfig,
 }
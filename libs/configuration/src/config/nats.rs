@@ -1,11 +1,34 @@
-use std::time::Duration;
+use std::{fmt, time::Duration};
 
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct NatsConfig {
     pub enabled: bool,
+    /// Server URL; a comma-separated list is accepted for clusters.
     pub url: String,
+
+    /// Additional seed servers, tried alongside `url`.
+    #[serde(default)]
+    pub servers: Vec<String>,
+
+    /// How long to wait for a connection to be established (seconds).
+    #[serde(default = "NatsConfig::default_connection_timeout")]
+    pub connection_timeout_secs: u64,
+
+    /// Prefix for request reply subjects (default `_INBOX`), for accounts
+    /// whose permissions only allow a custom inbox.
+    #[serde(default)]
+    pub inbox_prefix: Option<String>,
+
+    /// Credentials. Leave unset for an unauthenticated server.
+    #[serde(default)]
+    pub auth: Option<NatsAuthConfig>,
+
+    /// TLS / mTLS settings. Leave unset for plaintext (or `tls://` URLs
+    /// with the system roots).
+    #[serde(default)]
+    pub tls: Option<NatsTlsConfig>,
     /// Subject prefix prepended to every topic.
     /// e.g. `base_path = "myapp"` → `"user.created"` → `"myapp.user.created"`
     /// Leave empty to disable prefixing.
@@ -22,8 +45,373 @@ pub struct NatsConfig {
     /// Maximum reconnect attempts (-1 = infinite).
     #[serde(default = "NatsConfig::default_max_reconnects")]
     pub max_reconnects: i32,
+
+    /// Default payload codec: `"json"`, `"cbor"` or `"msgpack"`.
+    /// Inbound messages are always decoded by their `content-type` header.
+    #[serde(default = "NatsConfig::default_codec")]
+    pub codec: String,
+
+    /// Handlers running at once per subscription (0 = unlimited).
+    /// Further messages wait, so the subscription stops pulling.
+    #[serde(default = "NatsConfig::default_max_concurrency")]
+    pub max_concurrency: usize,
+
+    /// Report a slow consumer when a message waits this long for a free
+    /// handler slot (milliseconds).
+    #[serde(default = "NatsConfig::default_slow_consumer_threshold")]
+    pub slow_consumer_threshold_ms: u64,
+
+    /// Cancel a handler that runs longer than this (seconds, 0 = no limit).
+    /// With JetStream keep it below `jetstream.ack_wait_secs`, or the
+    /// message is redelivered while the handler still runs.
+    #[serde(default)]
+    pub handler_timeout_secs: u64,
+
+    /// On close, how long running handlers may take to finish before they
+    /// are aborted (seconds).
+    #[serde(default = "NatsConfig::default_drain_timeout")]
+    pub drain_timeout_secs: u64,
+
+    /// JetStream stream/consumer settings. Leave unset to use core NATS only.
+    #[serde(default)]
+    pub jetstream: Option<JetStreamConfig>,
+
+    /// Handler retry / dead-letter policy. Leave unset to disable retries.
+    #[serde(default)]
+    pub retry: Option<RetryConfig>,
+
+    /// Consumer de-duplication by message id. Leave unset to disable.
+    #[serde(default)]
+    pub idempotency: Option<IdempotencyConfig>,
+
+    /// Run singleton jobs (e.g. the outbox relay) on one replica only,
+    /// elected through a JetStream KV bucket. Leave unset to run them
+    /// everywhere.
+    #[serde(default)]
+    pub leader_election: Option<LeaderElectionConfig>,
+
+    /// Ordered processing by partition key: messages with a
+    /// `partition-key` attr are published to `<topic>.<n>`. Leave unset
+    /// to publish every message on its topic.
+    ///
+    /// Keyed messages no longer reach plain subscribers of `<topic>`; they
+    /// must subscribe to `<topic>.*` or use a `PartitionedConsumer`.
+    #[serde(default)]
+    pub partitioning: Option<PartitioningConfig>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PartitioningConfig {
+    /// Partitions per topic. Publishers and consumers must agree on it.
+    #[serde(default = "PartitioningConfig::default_partitions")]
+    pub partitions: u32,
+    /// KV bucket holding consumer membership and partition leases.
+    #[serde(default = "PartitioningConfig::default_bucket")]
+    pub bucket: String,
+    /// Membership / lease length (seconds); partitions of a dead consumer
+    /// move to the others within this time.
+    #[serde(default = "PartitioningConfig::default_ttl")]
+    pub ttl_secs: u64,
+}
+
+impl PartitioningConfig {
+    fn default_partitions() -> u32 {
+        16
+    }
+    fn default_bucket() -> String {
+        "partitions".to_string()
+    }
+    fn default_ttl() -> u64 {
+        15
+    }
+
+    pub fn ttl(&self) -> Duration {
+        Duration::from_secs(self.ttl_secs)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct LeaderElectionConfig {
+    /// KV bucket holding the leases.
+    #[serde(default = "LeaderElectionConfig::default_bucket")]
+    pub bucket: String,
+    /// Lease length (seconds); a dead leader is replaced within this time.
+    #[serde(default = "LeaderElectionConfig::default_ttl")]
+    pub ttl_secs: u64,
+}
+
+impl LeaderElectionConfig {
+    fn default_bucket() -> String {
+        "leaders".to_string()
+    }
+    fn default_ttl() -> u64 {
+        15
+    }
+
+    pub fn ttl(&self) -> Duration {
+        Duration::from_secs(self.ttl_secs)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct IdempotencyConfig {
+    /// How long a processed message id is remembered (seconds).
+    #[serde(default = "IdempotencyConfig::default_ttl")]
+    pub ttl_secs: u64,
+
+    /// How long a message being processed stays claimed if its consumer
+    /// dies (seconds); keep it above `handler_timeout_secs`.
+    #[serde(default = "IdempotencyConfig::default_lease")]
+    pub lease_secs: u64,
+
+    /// How often expired ids are deleted (seconds); with leader election
+    /// only the leader purges.
+    #[serde(default = "IdempotencyConfig::default_purge_interval")]
+    pub purge_interval_secs: u64,
+}
+
+impl IdempotencyConfig {
+    fn default_ttl() -> u64 {
+        86_400
+    }
+
+    fn default_lease() -> u64 {
+        60
+    }
+
+    fn default_purge_interval() -> u64 {
+        3_600
+    }
+
+    pub fn ttl(&self) -> Duration {
+        Duration::from_secs(self.ttl_secs)
+    }
+
+    pub fn lease(&self) -> Duration {
+        Duration::from_secs(self.lease_secs)
+    }
+
+    pub fn purge_interval(&self) -> Duration {
+        Duration::from_secs(self.purge_interval_secs)
+    }
 }
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: Self::default_ttl(),
+            lease_secs: Self::default_lease(),
+            purge_interval_secs: Self::default_purge_interval(),
+        }
+    }
+}
+
+/// NATS credentials, applied in order of precedence: `creds_file`, NKey
+/// seed, token, then user/password.
+///
+/// Every secret can be given inline or as a `*_file` path (e.g. a mounted
+/// Kubernetes secret); the inline value wins when both are set.
+#[derive(Deserialize, Serialize, Clone, Default)]
+pub struct NatsAuthConfig {
+    #[serde(default)]
+    pub user: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    #[serde(default)]
+    pub password_file: Option<String>,
+
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(default)]
+    pub token_file: Option<String>,
+
+    /// NKey seed (`SU...`).
+    #[serde(default)]
+    pub nkey_seed: Option<String>,
+    #[serde(default)]
+    pub nkey_seed_file: Option<String>,
+
+    /// Decentralized auth `.creds` file (user JWT + NKey seed).
+    #[serde(default)]
+    pub creds_file: Option<String>,
+}
+
+/// Secrets are redacted so the config can be logged.
+impl fmt::Debug for NatsAuthConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let redact = |v: &Option<String>| v.as_ref().map(|_| "<redacted>");
+        f.debug_struct("NatsAuthConfig")
+            .field("user", &self.user)
+            .field("password", &redact(&self.password))
+            .field("password_file", &self.password_file)
+            .field("token", &redact(&self.token))
+            .field("token_file", &self.token_file)
+            .field("nkey_seed", &redact(&self.nkey_seed))
+            .field("nkey_seed_file", &self.nkey_seed_file)
+            .field("creds_file", &self.creds_file)
+            .finish()
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct NatsTlsConfig {
+    /// Refuse to connect without TLS.
+    #[serde(default = "NatsTlsConfig::default_required")]
+    pub required: bool,
+
+    /// PEM file with root CAs to trust (private cluster CA).
+    #[serde(default)]
+    pub ca_file: Option<String>,
+
+    /// PEM client certificate and key for mTLS; set both or neither.
+    #[serde(default)]
+    pub cert_file: Option<String>,
+    #[serde(default)]
+    pub key_file: Option<String>,
+
+    /// Start TLS before the NATS handshake (server `handshake_first`).
+    #[serde(default)]
+    pub handshake_first: bool,
+}
+
+impl NatsTlsConfig {
+    fn default_required() -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RetryConfig {
+    /// Total handler attempts, including the first.
+    #[serde(default = "RetryConfig::default_max_attempts")]
+    pub max_attempts: u32,
+
+    /// Delay before the first retry (milliseconds).
+    #[serde(default = "RetryConfig::default_initial_backoff")]
+    pub initial_backoff_ms: u64,
+
+    /// Upper bound for the backoff delay (milliseconds).
+    #[serde(default = "RetryConfig::default_max_backoff")]
+    pub max_backoff_ms: u64,
+
+    /// Backoff growth factor between attempts.
+    #[serde(default = "RetryConfig::default_multiplier")]
+    pub multiplier: f64,
+
+    /// Random +/- fraction applied to each delay (0.0 – 1.0).
+    #[serde(default = "RetryConfig::default_jitter")]
+    pub jitter: f64,
+
+    /// Dead-letter subject prefix: `"dlq"` → `"dlq.<subject>"`.
+    /// Leave empty to drop messages after the final attempt.
+    #[serde(default = "RetryConfig::default_dead_letter_prefix")]
+    pub dead_letter_prefix: String,
+}
+
+impl RetryConfig {
+    fn default_max_attempts() -> u32 {
+        3
+    }
+    fn default_initial_backoff() -> u64 {
+        100
+    }
+    fn default_max_backoff() -> u64 {
+        10_000
+    }
+    fn default_multiplier() -> f64 {
+        2.0
+    }
+    fn default_jitter() -> f64 {
+        0.2
+    }
+    fn default_dead_letter_prefix() -> String {
+        "dlq".to_string()
+    }
+
+    pub fn initial_backoff(&self) -> Duration {
+        Duration::from_millis(self.initial_backoff_ms)
+    }
+
+    pub fn max_backoff(&self) -> Duration {
+        Duration::from_millis(self.max_backoff_ms)
+    }
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: Self::default_max_attempts(),
+            initial_backoff_ms: Self::default_initial_backoff(),
+            max_backoff_ms: Self::default_max_backoff(),
+            multiplier: Self::default_multiplier(),
+            jitter: Self::default_jitter(),
+            dead_letter_prefix: Self::default_dead_letter_prefix(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct JetStreamConfig {
+    /// Stream name, e.g. `"EVENTS"`.
+    pub stream: String,
+    /// Subjects captured by the stream (`base_path` is applied to each).
+    /// e.g. `["user.>"]`
+    pub subjects: Vec<String>,
+
+    /// `"file"` or `"memory"`.
+    #[serde(default = "JetStreamConfig::default_storage")]
+    pub storage: String,
+
+    #[serde(default = "JetStreamConfig::default_replicas")]
+    pub replicas: usize,
+
+    /// Maximum age of stored messages (seconds, 0 = unlimited).
+    #[serde(default)]
+    pub max_age_secs: u64,
+
+    /// How long the server waits for an ack before redelivering (seconds).
+    #[serde(default = "JetStreamConfig::default_ack_wait")]
+    pub ack_wait_secs: u64,
+
+    /// Maximum delivery attempts per message (-1 = unlimited).
+    #[serde(default = "JetStreamConfig::default_max_deliver")]
+    pub max_deliver: i64,
+
+    /// Maximum unacknowledged messages per consumer.
+    #[serde(default = "JetStreamConfig::default_max_ack_pending")]
+    pub max_ack_pending: i64,
+}
+
+impl JetStreamConfig {
+    fn default_storage() -> String {
+        "file".to_string()
+    }
+    fn default_replicas() -> usize {
+        1
+    }
+    fn default_ack_wait() -> u64 {
+        30
+    }
+    fn default_max_deliver() -> i64 {
+        -1
+    }
+    fn default_max_ack_pending() -> i64 {
+        1000
+    }
+
+    pub fn max_age(&self) -> Duration {
+        Duration::from_secs(self.max_age_secs)
+    }
+
+    pub fn ack_wait(&self) -> Duration {
+        Duration::from_secs(self.ack_wait_secs)
+    }
+}
+
 impl NatsConfig {
+    fn default_connection_timeout() -> u64 {
+        5
+    }
     fn default_ping_interval() -> u64 {
         20
     }
@@ -33,10 +421,50 @@ impl NatsConfig {
     fn default_max_reconnects() -> i32 {
         -1
     }
+    fn default_codec() -> String {
+        "json".to_string()
+    }
+    fn default_max_concurrency() -> usize {
+        100
+    }
+    fn default_slow_consumer_threshold() -> u64 {
+        5_000
+    }
+    fn default_drain_timeout() -> u64 {
+        30
+    }
 
     pub fn ping_interval(&self) -> Duration {
         Duration::from_secs(self.ping_interval_secs)
     }
+
+    pub fn connection_timeout(&self) -> Duration {
+        Duration::from_secs(self.connection_timeout_secs)
+    }
+
+    /// `url` (split on commas) followed by `servers`.
+    pub fn server_urls(&self) -> Vec<String> {
+        self.url
+            .split(',')
+            .chain(self.servers.iter().map(String::as_str))
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
+    pub fn slow_consumer_threshold(&self) -> Duration {
+        Duration::from_millis(self.slow_consumer_threshold_ms)
+    }
+
+    /// `None` when `handler_timeout_secs` is 0.
+    pub fn handler_timeout(&self) -> Option<Duration> {
+        (self.handler_timeout_secs > 0).then(|| Duration::from_secs(self.handler_timeout_secs))
+    }
+
+    pub fn drain_timeout(&self) -> Duration {
+        Duration::from_secs(self.drain_timeout_secs)
+    }
 }
 
 impl Default for NatsConfig {
@@ -44,10 +472,25 @@ impl Default for NatsConfig {
         Self {
             enabled: true,
             url: "nats://localhost:4222".to_string(),
+            servers: Vec::new(),
+            connection_timeout_secs: Self::default_connection_timeout(),
+            inbox_prefix: None,
+            auth: None,
+            tls: None,
             base_path: String::new(),
             ping_interval_secs: Self::default_ping_interval(),
             allow_reconnect: Self::default_allow_reconnect(),
             max_reconnects: Self::default_max_reconnects(),
+            codec: Self::default_codec(),
+            max_concurrency: Self::default_max_concurrency(),
+            slow_consumer_threshold_ms: Self::default_slow_consumer_threshold(),
+            handler_timeout_secs: 0,
+            drain_timeout_secs: Self::default_drain_timeout(),
+            jetstream: None,
+            retry: None,
+            idempotency: None,
+            leader_election: None,
+            partitioning: None,
         }
     }
 }
@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct OutboxConfig {
+    /// How often the relay polls for pending events (milliseconds).
+    #[serde(default = "OutboxConfig::default_poll_interval")]
+    pub poll_interval_ms: u64,
+
+    /// Maximum events published per poll.
+    #[serde(default = "OutboxConfig::default_batch_size")]
+    pub batch_size: u64,
+
+    /// Publish attempts before an event is marked `failed`.
+    #[serde(default = "OutboxConfig::default_max_attempts")]
+    pub max_attempts: i32,
+
+    /// Delay before the first retry (milliseconds), doubled per attempt.
+    #[serde(default = "OutboxConfig::default_initial_backoff")]
+    pub initial_backoff_ms: u64,
+
+    /// Upper bound for the retry delay (milliseconds).
+    #[serde(default = "OutboxConfig::default_max_backoff")]
+    pub max_backoff_ms: u64,
+}
+
+impl OutboxConfig {
+    fn default_poll_interval() -> u64 {
+        500
+    }
+    fn default_batch_size() -> u64 {
+        100
+    }
+    fn default_max_attempts() -> i32 {
+        10
+    }
+    fn default_initial_backoff() -> u64 {
+        1_000
+    }
+    fn default_max_backoff() -> u64 {
+        300_000
+    }
+
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.poll_interval_ms)
+    }
+
+    /// Delay before retry number `attempt` (1-based).
+    pub fn backoff(&self, attempt: i32) -> Duration {
+        let exp = attempt.saturating_sub(1).clamp(0, 31) as u32;
+        let delay = self.initial_backoff_ms.saturating_mul(1u64 << exp);
+        Duration::from_millis(delay.min(self.max_backoff_ms))
+    }
+}
+
+impl Default for OutboxConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval_ms: Self::default_poll_interval(),
+            batch_size: Self::default_batch_size(),
+            max_attempts: Self::default_max_attempts(),
+            initial_backoff_ms: Self::default_initial_backoff(),
+            max_backoff_ms: Self::default_max_backoff(),
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use ro_common::context::{RequestContext, SYSTEM_ACTOR};
+
 #[derive(Debug, Clone)]
 pub struct DbContext {
     pub id: String,
@@ -10,7 +12,12 @@ impl DbContext {
 
     pub fn system() -> Self {
         Self {
-            id: "system".to_string(),
+            id: SYSTEM_ACTOR.to_string(),
         }
     }
+
+    /// Actor of the current `RequestContext`, falling back to `system`.
+    pub fn current() -> Self {
+        Self::new(RequestContext::current().actor_or_system())
+    }
 }
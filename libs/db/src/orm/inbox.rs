@@ -0,0 +1,126 @@
+//! Inbox of processed message ids, for idempotent consumers.
+//!
+//! A row is a claim on one message for one consumer: a processing lease
+//! until `completed_at` is set, then a record of the processed message. It
+//! expires at `expires_at`, after which the same key may be claimed again.
+//!
+//! ```sql
+//! CREATE TABLE inbox (
+//!     id           VARCHAR PRIMARY KEY,
+//!     expires_at   TIMESTAMPTZ NOT NULL,
+//!     created_at   TIMESTAMPTZ NOT NULL,
+//!     completed_at TIMESTAMPTZ
+//! );
+//! CREATE INDEX inbox_expires_at_idx ON inbox (expires_at);
+//! ```
+
+use chrono::{DateTime, FixedOffset, TimeDelta, Utc};
+use sea_orm::{
+    ActiveValue::Set,
+    ConnectionTrait, DbErr,
+    entity::prelude::*,
+    sea_query::{Expr, OnConflict},
+};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "inbox")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: String,
+    pub expires_at: DateTimeWithTimeZone,
+    pub created_at: DateTimeWithTimeZone,
+    pub completed_at: Option<DateTimeWithTimeZone>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// Claim `key` for processing until `lease` from now.
+///
+/// Returns `None` when claimed, else the existing unexpired claim (done if
+/// `completed_at` is set). An expired claim is taken over in the same
+/// statement, so concurrent callers never both win. A `lease` past the
+/// largest timestamp is an error.
+pub async fn try_claim<C: ConnectionTrait>(
+    db: &C,
+    key: &str,
+    lease: std::time::Duration,
+) -> Result<Option<Model>, DbErr> {
+    loop {
+        let now: DateTime<FixedOffset> = Utc::now().into();
+        let row = ActiveModel {
+            id: Set(key.to_string()),
+            expires_at: Set(expires_after(now, lease)?),
+            created_at: Set(now),
+            completed_at: Set(None),
+        };
+
+        let affected = Entity::insert(row)
+            .on_conflict(
+                OnConflict::column(Column::Id)
+                    .update_columns([Column::ExpiresAt, Column::CreatedAt, Column::CompletedAt])
+                    .action_and_where(Expr::col((Entity, Column::ExpiresAt)).lte(now))
+                    .to_owned(),
+            )
+            .exec_without_returning(db)
+            .await?;
+        if affected > 0 {
+            return Ok(None);
+        }
+        // Released between the insert and the read: try again.
+        if let Some(claim) = Entity::find_by_id(key.to_string()).one(db).await? {
+            return Ok(Some(claim));
+        }
+    }
+}
+
+/// Mark `key` processed and keep it until `ttl` from now.
+pub async fn complete<C: ConnectionTrait>(
+    db: &C,
+    key: &str,
+    ttl: std::time::Duration,
+) -> Result<(), DbErr> {
+    let now: DateTime<FixedOffset> = Utc::now().into();
+    let row = ActiveModel {
+        id: Set(key.to_string()),
+        expires_at: Set(expires_after(now, ttl)?),
+        created_at: Set(now),
+        completed_at: Set(Some(now)),
+    };
+    Entity::insert(row)
+        .on_conflict(
+            OnConflict::column(Column::Id)
+                .update_columns([Column::ExpiresAt, Column::CompletedAt])
+                .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await?;
+    Ok(())
+}
+
+fn expires_after(
+    now: DateTime<FixedOffset>,
+    ttl: std::time::Duration,
+) -> Result<DateTime<FixedOffset>, DbErr> {
+    TimeDelta::from_std(ttl)
+        .ok()
+        .and_then(|ttl| now.checked_add_signed(ttl))
+        .ok_or_else(|| DbErr::Custom(format!("inbox ttl out of range: {ttl:?}")))
+}
+
+pub async fn release<C: ConnectionTrait>(db: &C, key: &str) -> Result<(), DbErr> {
+    Entity::delete_by_id(key.to_string()).exec(db).await?;
+    Ok(())
+}
+
+/// Delete expired claims; returns how many rows were removed.
+pub async fn purge_expired<C: ConnectionTrait>(db: &C) -> Result<u64, DbErr> {
+    let now: DateTime<FixedOffset> = Utc::now().into();
+    let res = Entity::delete_many()
+        .filter(Column::ExpiresAt.lte(now))
+        .exec(db)
+        .await?;
+    Ok(res.rows_affected)
+}
@@ -1,6 +1,8 @@
 pub mod audit;
 pub mod context;
 pub mod dto;
+pub mod inbox;
+pub mod outbox;
 pub mod repo;
 
 mod init;
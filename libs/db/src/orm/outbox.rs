@@ -0,0 +1,177 @@
+//! Transactional outbox.
+//!
+//! Events are inserted into `outbox` in the same transaction as the entity
+//! that produced them (see `Repository::create_with_outbox`); a relay then
+//! publishes pending rows and marks them sent.
+//!
+//! A row with a future `available_at` is a scheduled message: the relay
+//! leaves it until it is due, and it can be cancelled until then.
+//!
+//! ```sql
+//! CREATE TABLE outbox (
+//!     id           VARCHAR PRIMARY KEY,
+//!     topic        VARCHAR     NOT NULL,
+//!     payload      BYTEA       NOT NULL,
+//!     headers      JSONB       NOT NULL DEFAULT '{}',
+//!     status       VARCHAR     NOT NULL DEFAULT 'pending',
+//!     attempts     INTEGER     NOT NULL DEFAULT 0,
+//!     last_error   TEXT,
+//!     available_at TIMESTAMPTZ NOT NULL,
+//!     sent_at      TIMESTAMPTZ,
+//!     created_at   TIMESTAMPTZ NOT NULL,
+//!     created_by   VARCHAR     NOT NULL
+//! );
+//! CREATE INDEX outbox_pending_idx ON outbox (available_at) WHERE status = 'pending';
+//! ```
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, FixedOffset, Utc};
+use sea_orm::{
+    ActiveValue::Set, ConnectionTrait, DbErr, QueryOrder, QuerySelect, entity::prelude::*,
+    sea_query::Expr, sea_query::LockBehavior, sea_query::LockType,
+};
+
+use ro_common::id::generate_nanoid;
+
+use crate::make_creatable;
+
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_SENT: &str = "sent";
+pub const STATUS_FAILED: &str = "failed";
+pub const STATUS_CANCELLED: &str = "cancelled";
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "outbox")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: String,
+    pub topic: String,
+    pub payload: Vec<u8>,
+    pub headers: Json,
+    pub status: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    /// Earliest time the relay may (re)publish this row.
+    pub available_at: DateTimeWithTimeZone,
+    pub sent_at: Option<DateTimeWithTimeZone>,
+    pub created_at: DateTimeWithTimeZone,
+    pub created_by: String,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+make_creatable!(ActiveModel);
+
+impl Model {
+    /// Stored headers as a flat string map.
+    pub fn header_map(&self) -> HashMap<String, String> {
+        serde_json::from_value(self.headers.clone()).unwrap_or_default()
+    }
+}
+
+/// An event to be written to the outbox.
+#[derive(Debug, Clone)]
+pub struct OutboxEvent {
+    pub topic: String,
+    pub payload: Vec<u8>,
+    pub headers: HashMap<String, String>,
+    /// Publish no earlier than this; `None` for as soon as possible.
+    pub available_at: Option<DateTime<Utc>>,
+}
+
+impl OutboxEvent {
+    pub fn new(topic: impl Into<String>, payload: impl Into<Vec<u8>>) -> Self {
+        Self {
+            topic: topic.into(),
+            payload: payload.into(),
+            headers: HashMap::new(),
+            available_at: None,
+        }
+    }
+
+    pub fn with_available_at(mut self, at: DateTime<Utc>) -> Self {
+        self.available_at = Some(at);
+        self
+    }
+}
+
+impl From<OutboxEvent> for ActiveModel {
+    fn from(value: OutboxEvent) -> Self {
+        let now: DateTime<FixedOffset> = Utc::now().into();
+        Self {
+            id: Set(generate_nanoid()),
+            topic: Set(value.topic),
+            payload: Set(value.payload),
+            headers: Set(serde_json::to_value(value.headers).unwrap_or_default()),
+            status: Set(STATUS_PENDING.to_string()),
+            attempts: Set(0),
+            last_error: Set(None),
+            available_at: Set(value.available_at.map_or(now, Into::into)),
+            sent_at: Set(None),
+            ..Default::default()
+        }
+    }
+}
+
+/// Pending rows whose `available_at` has passed, oldest first.
+///
+/// Rows are locked `FOR UPDATE SKIP LOCKED`, so call this inside a
+/// transaction: concurrent relays then never pick the same row.
+pub async fn fetch_due<C: ConnectionTrait>(db: &C, limit: u64) -> Result<Vec<Model>, DbErr> {
+    let now: DateTime<FixedOffset> = Utc::now().into();
+    Entity::find()
+        .filter(Column::Status.eq(STATUS_PENDING))
+        .filter(Column::AvailableAt.lte(now))
+        .order_by_asc(Column::AvailableAt)
+        .limit(limit)
+        .lock_with_behavior(LockType::Update, LockBehavior::SkipLocked)
+        .all(db)
+        .await
+}
+
+/// Cancel a pending row. Returns `false` if there is no such row or it was
+/// already sent.
+///
+/// A row the relay is publishing is locked, so this waits for the relay's
+/// transaction and then finds it sent.
+pub async fn cancel<C: ConnectionTrait>(db: &C, id: &str) -> Result<bool, DbErr> {
+    let res = Entity::update_many()
+        .col_expr(Column::Status, Expr::value(STATUS_CANCELLED))
+        .filter(Column::Id.eq(id))
+        .filter(Column::Status.eq(STATUS_PENDING))
+        .exec(db)
+        .await?;
+    Ok(res.rows_affected > 0)
+}
+
+pub async fn mark_sent<C: ConnectionTrait>(db: &C, model: Model) -> Result<Model, DbErr> {
+    let attempts = model.attempts + 1;
+    let mut active: ActiveModel = model.into();
+    active.status = Set(STATUS_SENT.to_string());
+    active.attempts = Set(attempts);
+    active.sent_at = Set(Some(Utc::now().into()));
+    active.update(db).await
+}
+
+/// Record a failed publish; the row is retried at `retry_at`, or marked
+/// `failed` for good when `retry_at` is `None`.
+pub async fn mark_failed<C: ConnectionTrait>(
+    db: &C,
+    model: Model,
+    error: String,
+    retry_at: Option<DateTime<Utc>>,
+) -> Result<Model, DbErr> {
+    let attempts = model.attempts + 1;
+    let mut active: ActiveModel = model.into();
+    active.attempts = Set(attempts);
+    active.last_error = Set(Some(error));
+    match retry_at {
+        Some(at) => active.available_at = Set(at.into()),
+        None => active.status = Set(STATUS_FAILED.to_string()),
+    }
+    active.update(db).await
+}
@@ -2,13 +2,14 @@ use std::sync::Arc;
 
 use sea_orm::{
     ActiveModelBehavior, ConnectionTrait, DbErr, DeleteResult, EntityTrait, IntoActiveModel,
-    PaginatorTrait, Selector,
+    PaginatorTrait, Selector, TransactionTrait,
 };
 
 use crate::orm::{
     audit::{Creatable, Deletable, Updatable},
     context::DbContext,
     dto::{ResFilterResultDto, ResultPagination},
+    outbox::{self, OutboxEvent},
 };
 
 #[derive(Debug, Clone)]
@@ -34,6 +35,37 @@ impl<C: ConnectionTrait> Repository<C> {
         entity.insert(self.db.as_ref()).await
     }
 
+    /// Insert `entity` and its `events` into the outbox in one transaction,
+    /// so an event exists if and only if the entity was written.
+    pub async fn create_with_outbox<E>(
+        &self,
+        ctx: &DbContext,
+        mut entity: E,
+        events: Vec<OutboxEvent>,
+    ) -> Result<<E::Entity as EntityTrait>::Model, DbErr>
+    where
+        C: TransactionTrait,
+        <E::Entity as EntityTrait>::Model: IntoActiveModel<E>,
+        E: Creatable + ActiveModelBehavior + Send,
+    {
+        let txn = self.db.begin().await?;
+
+        entity.fill_create_audit(ctx.id.clone());
+        let model = entity.insert(&txn).await?;
+
+        if !events.is_empty() {
+            let rows = events.into_iter().map(|event| {
+                let mut row: outbox::ActiveModel = event.into();
+                row.fill_create_audit(ctx.id.clone());
+                row
+            });
+            outbox::Entity::insert_many(rows).exec(&txn).await?;
+        }
+
+        txn.commit().await?;
+        Ok(model)
+    }
+
     pub async fn create_many<E>(&self, ctx: &DbContext, mut entities: Vec<E>) -> Result<(), DbErr>
     where
         <E::Entity as EntityTrait>::Model: IntoActiveModel<E>,
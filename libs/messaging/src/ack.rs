@@ -0,0 +1,127 @@
+use std::{
+    future::Future,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+
+use crate::MessagingError;
+
+/// How an inbound message is settled with the transport.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AckKind {
+    /// Processed successfully — never redeliver.
+    Ack,
+    /// Not processed — redeliver, optionally after `delay`.
+    Nak(Option<Duration>),
+    /// Cannot ever be processed — stop redelivering without success.
+    Term,
+    /// Still working — extend the ack deadline. Does not settle the message.
+    InProgress,
+}
+
+/// Transport hook that delivers an `AckKind` to the server.
+#[async_trait]
+pub trait Acknowledger: Send + Sync {
+    async fn send(&self, kind: AckKind) -> Result<(), MessagingError>;
+}
+
+struct AckState {
+    acker: Box<dyn Acknowledger>,
+    settled: AtomicBool,
+}
+
+/// Per-delivery acknowledgement handle.
+///
+/// Shared between the subscription drain loop and the `Message` handed to
+/// the domain handler. Messages without one (core NATS) treat every call
+/// as a no-op.
+#[derive(Clone, Default)]
+pub struct AckHandle {
+    state: Option<Arc<AckState>>,
+}
+
+tokio::task_local! {
+    static CURRENT: AckHandle;
+}
+
+impl AckHandle {
+    pub fn new(acker: impl Acknowledger + 'static) -> Self {
+        Self {
+            state: Some(Arc::new(AckState {
+                acker: Box::new(acker),
+                settled: AtomicBool::new(false),
+            })),
+        }
+    }
+
+    /// `true` if the transport expects acks for this delivery.
+    pub fn is_tracked(&self) -> bool {
+        self.state.is_some()
+    }
+
+    /// `true` once `Ack`, `Nak` or `Term` has been sent.
+    pub fn is_settled(&self) -> bool {
+        self.state
+            .as_ref()
+            .is_some_and(|s| s.settled.load(Ordering::Acquire))
+    }
+
+    /// Send `kind` to the transport.
+    ///
+    /// A message is settled at most once; later `Ack`/`Nak`/`Term` calls are
+    /// ignored. `InProgress` may be sent any number of times before that.
+    pub async fn send(&self, kind: AckKind) -> Result<(), MessagingError> {
+        let Some(state) = &self.state else {
+            return Ok(());
+        };
+        if kind == AckKind::InProgress {
+            if state.settled.load(Ordering::Acquire) {
+                return Ok(());
+            }
+        } else if state.settled.swap(true, Ordering::AcqRel) {
+            return Ok(());
+        }
+        state.acker.send(kind).await
+    }
+
+    /// Settle from the handler result unless the handler already did:
+    /// `Ok` → `Ack`, retryable `Err` → `Nak`, any other `Err` → `Term`
+    /// (redelivering a poison message cannot help).
+    pub(crate) async fn settle(&self, result: &Result<(), MessagingError>) {
+        if !self.is_tracked() || self.is_settled() {
+            return;
+        }
+        let kind = match result {
+            Ok(()) => AckKind::Ack,
+            Err(e) if e.is_retryable() => AckKind::Nak(None),
+            Err(_) => AckKind::Term,
+        };
+        if let Err(e) = self.send(kind).await {
+            tracing::warn!(error = %e, ?kind, "ack failed");
+        }
+    }
+
+    /// Run `fut` with `self` as the current delivery's handle.
+    pub(crate) async fn scope<F: Future>(self, fut: F) -> F::Output {
+        CURRENT.scope(self, fut).await
+    }
+
+    /// The handle of the delivery being processed by this task, if any.
+    pub(crate) fn current() -> Self {
+        CURRENT.try_with(Clone::clone).unwrap_or_default()
+    }
+}
+
+impl std::fmt::Debug for AckHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AckHandle")
+            .field("tracked", &self.is_tracked())
+            .field("settled", &self.is_settled())
+            .finish()
+    }
+}
@@ -0,0 +1,459 @@
+//! CloudEvents 1.0 envelope (NATS protocol binding).
+//!
+//! - **Binary** mode: attributes travel as `ce-*` headers, `datacontenttype`
+//!   as `content-type`, and the payload is the event data as-is.
+//! - **Structured** mode: the whole event is a JSON body with
+//!   `content-type: application/cloudevents+json`; JSON data is inlined as
+//!   `data`, anything else is carried as `data_base64`.
+//!
+//! In both modes the event `id` is also written as `Nats-Msg-Id`, so
+//! JetStream and `idempotency_middleware` de-duplicate on it.
+
+use std::collections::HashMap;
+
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use bytes::Bytes;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{
+    Message, MessagingError,
+    codec::{Codec, Encoding, HEADER_CONTENT_TYPE},
+    nats::factory::HEADER_MESSAGE_ID,
+};
+
+pub const SPEC_VERSION: &str = "1.0";
+/// `content-type` of a structured-mode event.
+pub const STRUCTURED_CONTENT_TYPE: &str = "application/cloudevents+json";
+
+const HEADER_PREFIX: &str = "ce-";
+const ATTR_SPEC_VERSION: &str = "specversion";
+const ATTR_ID: &str = "id";
+const ATTR_SOURCE: &str = "source";
+const ATTR_TYPE: &str = "type";
+const ATTR_SUBJECT: &str = "subject";
+const ATTR_TIME: &str = "time";
+
+/// How a `CloudEvent` is laid out on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CloudEventMode {
+    #[default]
+    Binary,
+    Structured,
+}
+
+/// A CloudEvents 1.0 event.
+///
+/// Build one with `MessageFactory::cloud_event` (fills `id`, `source`,
+/// `time` and `datacontenttype`) or `CloudEvent::new`, then send it with
+/// `PublisherExt::publish_event`. Read one back with
+/// `CloudEvent::try_from(&msg)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CloudEvent {
+    pub id: String,
+    /// URI-reference identifying the producer, e.g. the service name.
+    pub source: String,
+    /// Event type, e.g. `"user.created"`.
+    pub ty: String,
+    pub subject: Option<String>,
+    pub time: Option<DateTime<Utc>>,
+    /// MIME type of `data`; JSON when absent.
+    pub datacontenttype: Option<String>,
+    pub data: Bytes,
+    /// Extension attributes (e.g. `traceparent`), by lowercase name.
+    pub extensions: HashMap<String, String>,
+}
+
+impl CloudEvent {
+    pub fn new(
+        id: impl Into<String>,
+        source: impl Into<String>,
+        ty: impl Into<String>,
+        data: impl Into<Bytes>,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            source: source.into(),
+            ty: ty.into(),
+            subject: None,
+            time: None,
+            datacontenttype: None,
+            data: data.into(),
+            extensions: HashMap::new(),
+        }
+    }
+
+    pub fn with_subject(mut self, subject: impl Into<String>) -> Self {
+        self.subject = Some(subject.into());
+        self
+    }
+
+    pub fn with_time(mut self, time: DateTime<Utc>) -> Self {
+        self.time = Some(time);
+        self
+    }
+
+    pub fn with_datacontenttype(mut self, content_type: impl Into<String>) -> Self {
+        self.datacontenttype = Some(content_type.into());
+        self
+    }
+
+    pub fn with_extension(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.extensions
+            .insert(name.into().to_ascii_lowercase(), value.into());
+        self
+    }
+
+    /// Decode `data` with the codec named by `datacontenttype`.
+    pub fn data_as<T: DeserializeOwned>(&self) -> Result<T, MessagingError> {
+        self.encoding()?.decode(&self.data)
+    }
+
+    fn encoding(&self) -> Result<Encoding, MessagingError> {
+        match &self.datacontenttype {
+            None => Ok(Encoding::Json),
+            Some(ct) => Encoding::from_content_type(ct)
+                .ok_or_else(|| MessagingError::decode(format!("unsupported datacontenttype {ct}"))),
+        }
+    }
+
+    fn is_json(&self) -> bool {
+        self.datacontenttype
+            .as_deref()
+            .is_none_or(|ct| Encoding::from_content_type(ct) == Some(Encoding::Json))
+    }
+
+    /// Lay the event out as a `Message` on `topic`.
+    pub fn into_message(
+        self,
+        topic: impl Into<String>,
+        mode: CloudEventMode,
+    ) -> Result<Message, MessagingError> {
+        let id = self.id.clone();
+        let msg = match mode {
+            CloudEventMode::Binary => self.into_binary(topic),
+            CloudEventMode::Structured => self.into_structured(topic)?,
+        };
+        Ok(msg.with_attr(HEADER_MESSAGE_ID, id))
+    }
+
+    fn into_binary(self, topic: impl Into<String>) -> Message {
+        let mut msg = Message::new(topic, self.data)
+            .with_attr(ce_header(ATTR_SPEC_VERSION), SPEC_VERSION)
+            .with_attr(ce_header(ATTR_ID), self.id)
+            .with_attr(ce_header(ATTR_SOURCE), self.source)
+            .with_attr(ce_header(ATTR_TYPE), self.ty);
+        if let Some(subject) = self.subject {
+            msg = msg.with_attr(ce_header(ATTR_SUBJECT), subject);
+        }
+        if let Some(time) = self.time {
+            msg = msg.with_attr(ce_header(ATTR_TIME), format_time(time));
+        }
+        if let Some(ct) = self.datacontenttype {
+            msg = msg.with_attr(HEADER_CONTENT_TYPE, ct);
+        }
+        for (name, value) in self.extensions {
+            msg = msg.with_attr(ce_header(&name), value);
+        }
+        msg
+    }
+
+    fn into_structured(self, topic: impl Into<String>) -> Result<Message, MessagingError> {
+        let (data, data_base64) = if self.data.is_empty() {
+            (None, None)
+        } else if self.is_json() {
+            let value = serde_json::from_slice(&self.data).map_err(MessagingError::encode)?;
+            (Some(value), None)
+        } else {
+            (None, Some(BASE64.encode(&self.data)))
+        };
+
+        let body = Structured {
+            specversion: SPEC_VERSION.to_string(),
+            id: self.id,
+            source: self.source,
+            ty: self.ty,
+            subject: self.subject,
+            time: self.time.map(format_time),
+            datacontenttype: self.datacontenttype,
+            data,
+            data_base64,
+            extensions: self
+                .extensions
+                .into_iter()
+                .map(|(k, v)| (k, serde_json::Value::String(v)))
+                .collect(),
+        };
+        let payload = serde_json::to_vec(&body).map_err(MessagingError::encode)?;
+
+        Ok(Message::new(topic, payload).with_attr(HEADER_CONTENT_TYPE, STRUCTURED_CONTENT_TYPE))
+    }
+
+    fn from_binary(msg: &Message) -> Result<Self, MessagingError> {
+        let required = |name: &str| {
+            msg.attr(&ce_header(name))
+                .map(str::to_string)
+                .ok_or_else(|| {
+                    MessagingError::decode(format!("missing CloudEvent attribute {name}"))
+                })
+        };
+        check_spec_version(&required(ATTR_SPEC_VERSION)?)?;
+
+        let known = [
+            ATTR_SPEC_VERSION,
+            ATTR_ID,
+            ATTR_SOURCE,
+            ATTR_TYPE,
+            ATTR_SUBJECT,
+            ATTR_TIME,
+        ];
+        let extensions = msg
+            .attrs
+            .iter()
+            .filter_map(|(k, v)| {
+                let name = k
+                    .to_ascii_lowercase()
+                    .strip_prefix(HEADER_PREFIX)?
+                    .to_string();
+                (!known.contains(&name.as_str())).then(|| (name, v.clone()))
+            })
+            .collect();
+
+        Ok(Self {
+            id: required(ATTR_ID)?,
+            source: required(ATTR_SOURCE)?,
+            ty: required(ATTR_TYPE)?,
+            subject: msg.attr(&ce_header(ATTR_SUBJECT)).map(str::to_string),
+            time: msg
+                .attr(&ce_header(ATTR_TIME))
+                .map(parse_time)
+                .transpose()?,
+            datacontenttype: msg.attr(HEADER_CONTENT_TYPE).map(str::to_string),
+            data: msg.data.clone(),
+            extensions,
+        })
+    }
+
+    fn from_structured(msg: &Message) -> Result<Self, MessagingError> {
+        let body: Structured = serde_json::from_slice(&msg.data).map_err(MessagingError::decode)?;
+        check_spec_version(&body.specversion)?;
+
+        let data = match (body.data, body.data_base64) {
+            (Some(value), _) => serde_json::to_vec(&value)
+                .map(Bytes::from)
+                .map_err(MessagingError::decode)?,
+            (None, Some(encoded)) => BASE64
+                .decode(encoded)
+                .map(Bytes::from)
+                .map_err(MessagingError::decode)?,
+            (None, None) => Bytes::new(),
+        };
+
+        Ok(Self {
+            id: body.id,
+            source: body.source,
+            ty: body.ty,
+            subject: body.subject,
+            time: body.time.as_deref().map(parse_time).transpose()?,
+            datacontenttype: body.datacontenttype,
+            data,
+            extensions: body
+                .extensions
+                .into_iter()
+                .map(|(k, v)| match v {
+                    serde_json::Value::String(s) => (k, s),
+                    other => (k, other.to_string()),
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Read an event in either mode, picked from the message's `content-type`.
+impl TryFrom<&Message> for CloudEvent {
+    type Error = MessagingError;
+
+    fn try_from(msg: &Message) -> Result<Self, Self::Error> {
+        let structured = msg.attr(HEADER_CONTENT_TYPE).is_some_and(|ct| {
+            ct.split(';')
+                .next()
+                .is_some_and(|mime| mime.trim().eq_ignore_ascii_case(STRUCTURED_CONTENT_TYPE))
+        });
+        if structured {
+            Self::from_structured(msg)
+        } else {
+            Self::from_binary(msg)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Structured {
+    specversion: String,
+    id: String,
+    source: String,
+    #[serde(rename = "type")]
+    ty: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    subject: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    time: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    datacontenttype: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    data: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    data_base64: Option<String>,
+    #[serde(flatten)]
+    extensions: HashMap<String, serde_json::Value>,
+}
+
+fn ce_header(name: &str) -> String {
+    format!("{HEADER_PREFIX}{name}")
+}
+
+fn check_spec_version(version: &str) -> Result<(), MessagingError> {
+    if version == SPEC_VERSION {
+        Ok(())
+    } else {
+        Err(MessagingError::decode(format!(
+            "unsupported CloudEvents specversion {version}"
+        )))
+    }
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn parse_time(value: &str) -> Result<DateTime<Utc>, MessagingError> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| MessagingError::decode(format!("invalid CloudEvent time: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::CodecDirection;
+
+    fn event(data: &'static [u8]) -> CloudEvent {
+        CloudEvent::new(
+            "evt-1",
+            "users-service",
+            "user.created",
+            Bytes::from_static(data),
+        )
+        .with_subject("user-42")
+        .with_time("2024-05-01T12:30:00.250Z".parse().unwrap())
+        .with_extension("TraceParent", "00-abc-def-01")
+    }
+
+    fn round_trip(event: CloudEvent, mode: CloudEventMode) -> (Message, CloudEvent) {
+        let msg = event.into_message("user.created", mode).unwrap();
+        let decoded = CloudEvent::try_from(&msg).unwrap();
+        (msg, decoded)
+    }
+
+    fn is_decode_error(err: &MessagingError) -> bool {
+        matches!(
+            err,
+            MessagingError::Codec {
+                direction: CodecDirection::Decode,
+                ..
+            }
+        )
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let original = event(br#"{"id":42}"#).with_datacontenttype("application/json");
+        let (msg, decoded) = round_trip(original.clone(), CloudEventMode::Binary);
+
+        assert_eq!(msg.attr("ce-specversion"), Some(SPEC_VERSION));
+        assert_eq!(msg.attr("ce-type"), Some("user.created"));
+        assert_eq!(msg.attr("ce-traceparent"), Some("00-abc-def-01"));
+        assert_eq!(msg.attr(HEADER_CONTENT_TYPE), Some("application/json"));
+        assert_eq!(msg.attr(HEADER_MESSAGE_ID), Some("evt-1"));
+        assert_eq!(msg.data, original.data);
+        assert_eq!(decoded, original);
+    }
+
+    #[test]
+    fn structured_round_trip_inlines_json_data() {
+        let original = event(br#"{"id":42}"#);
+        let (msg, decoded) = round_trip(original.clone(), CloudEventMode::Structured);
+
+        assert_eq!(msg.attr(HEADER_CONTENT_TYPE), Some(STRUCTURED_CONTENT_TYPE));
+        assert_eq!(msg.attr(HEADER_MESSAGE_ID), Some("evt-1"));
+        let body: serde_json::Value = serde_json::from_slice(&msg.data).unwrap();
+        assert_eq!(body["data"]["id"], 42);
+        assert_eq!(body["traceparent"], "00-abc-def-01");
+        assert!(body.get("data_base64").is_none());
+        assert_eq!(decoded, original);
+        assert_eq!(decoded.data_as::<serde_json::Value>().unwrap()["id"], 42);
+    }
+
+    #[test]
+    fn structured_round_trip_base64_encodes_binary_data() {
+        let original = event(&[0xa1, 0x62, 0x69, 0x64]).with_datacontenttype("application/cbor");
+        let (msg, decoded) = round_trip(original.clone(), CloudEventMode::Structured);
+
+        let body: serde_json::Value = serde_json::from_slice(&msg.data).unwrap();
+        assert!(body.get("data").is_none());
+        assert_eq!(body["data_base64"], BASE64.encode(&original.data));
+        assert_eq!(decoded, original);
+    }
+
+    #[test]
+    fn structured_content_type_with_parameters_is_recognised() {
+        let msg = event(b"{}")
+            .into_message("user.created", CloudEventMode::Structured)
+            .unwrap();
+        let msg = msg.with_attr(
+            HEADER_CONTENT_TYPE,
+            "Application/CloudEvents+JSON; charset=utf-8",
+        );
+        assert_eq!(CloudEvent::try_from(&msg).unwrap().id, "evt-1");
+    }
+
+    #[test]
+    fn binary_event_requires_core_attributes() {
+        for missing in [ATTR_SPEC_VERSION, ATTR_ID, ATTR_SOURCE, ATTR_TYPE] {
+            let mut msg = event(b"{}")
+                .into_message("user.created", CloudEventMode::Binary)
+                .unwrap();
+            msg.attrs.remove(&ce_header(missing));
+
+            let err = CloudEvent::try_from(&msg).unwrap_err();
+            assert!(is_decode_error(&err), "{missing}: {err:?}");
+            assert!(err.to_string().contains(missing), "{missing}: {err}");
+        }
+    }
+
+    #[test]
+    fn structured_event_requires_core_attributes() {
+        for missing in [ATTR_SPEC_VERSION, ATTR_ID, ATTR_SOURCE, ATTR_TYPE] {
+            let msg = event(b"{}")
+                .into_message("user.created", CloudEventMode::Structured)
+                .unwrap();
+            let mut body: serde_json::Value = serde_json::from_slice(&msg.data).unwrap();
+            body.as_object_mut().unwrap().remove(missing);
+            let msg = Message::new("user.created", serde_json::to_vec(&body).unwrap())
+                .with_attr(HEADER_CONTENT_TYPE, STRUCTURED_CONTENT_TYPE);
+
+            let err = CloudEvent::try_from(&msg).unwrap_err();
+            assert!(is_decode_error(&err), "{missing}: {err:?}");
+        }
+    }
+
+    #[test]
+    fn unsupported_spec_version_is_rejected() {
+        let msg = event(b"{}")
+            .into_message("user.created", CloudEventMode::Binary)
+            .unwrap()
+            .with_attr("ce-specversion", "0.3");
+        let err = CloudEvent::try_from(&msg).unwrap_err();
+        assert!(err.to_string().contains("specversion 0.3"), "{err}");
+    }
+}
@@ -0,0 +1,252 @@
+use std::{collections::HashMap, str::FromStr};
+
+use bytes::Bytes;
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::MessagingError;
+
+/// Header naming the payload encoding, e.g. `application/cbor`.
+pub const HEADER_CONTENT_TYPE: &str = "content-type";
+
+/// Payload serialization format.
+pub trait Codec: Send + Sync {
+    /// MIME type written to the `content-type` header.
+    fn content_type(&self) -> &'static str;
+
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Bytes, MessagingError>;
+
+    fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T, MessagingError>;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonCodec;
+
+impl Codec for JsonCodec {
+    fn content_type(&self) -> &'static str {
+        "application/json"
+    }
+
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Bytes, MessagingError> {
+        serde_json::to_vec(value)
+            .map(Bytes::from)
+            .map_err(MessagingError::encode)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T, MessagingError> {
+        serde_json::from_slice(data).map_err(MessagingError::decode)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CborCodec;
+
+impl Codec for CborCodec {
+    fn content_type(&self) -> &'static str {
+        "application/cbor"
+    }
+
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Bytes, MessagingError> {
+        serde_cbor::to_vec(value)
+            .map(Bytes::from)
+            .map_err(MessagingError::encode)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T, MessagingError> {
+        serde_cbor::from_slice(data).map_err(MessagingError::decode)
+    }
+}
+
+/// MessagePack with named struct fields, so payloads stay readable by
+/// consumers that don't share the Rust types.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MsgPackCodec;
+
+impl Codec for MsgPackCodec {
+    fn content_type(&self) -> &'static str {
+        "application/msgpack"
+    }
+
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Bytes, MessagingError> {
+        rmp_serde::to_vec_named(value)
+            .map(Bytes::from)
+            .map_err(MessagingError::encode)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T, MessagingError> {
+        rmp_serde::from_slice(data).map_err(MessagingError::decode)
+    }
+}
+
+/// Runtime choice of codec, picked from config or a `content-type` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    #[default]
+    Json,
+    Cbor,
+    MsgPack,
+}
+
+impl Encoding {
+    /// Match a `content-type` value; parameters such as `; charset=utf-8`
+    /// are ignored.
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime = content_type.split(';').next().unwrap_or_default().trim();
+        [Self::Json, Self::Cbor, Self::MsgPack]
+            .into_iter()
+            .find(|e| mime.eq_ignore_ascii_case(e.content_type()))
+    }
+
+    /// Encoding declared in `attrs`; JSON when no `content-type` is set.
+    pub fn from_attrs(attrs: &HashMap<String, String>) -> Result<Self, MessagingError> {
+        match attrs.get(HEADER_CONTENT_TYPE) {
+            None => Ok(Self::Json),
+            Some(ct) => Self::from_content_type(ct)
+                .ok_or_else(|| MessagingError::decode(format!("unsupported content-type {ct}"))),
+        }
+    }
+}
+
+/// Parse a config name: `"json"`, `"cbor"` or `"msgpack"`.
+impl FromStr for Encoding {
+    type Err = MessagingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "cbor" => Ok(Self::Cbor),
+            "msgpack" | "messagepack" => Ok(Self::MsgPack),
+            other => Self::from_content_type(other)
+                .ok_or_else(|| MessagingError::encode(format!("unknown codec {other}"))),
+        }
+    }
+}
+
+impl Codec for Encoding {
+    fn content_type(&self) -> &'static str {
+        match self {
+            Self::Json => JsonCodec.content_type(),
+            Self::Cbor => CborCodec.content_type(),
+            Self::MsgPack => MsgPackCodec.content_type(),
+        }
+    }
+
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Bytes, MessagingError> {
+        match self {
+            Self::Json => JsonCodec.encode(value),
+            Self::Cbor => CborCodec.encode(value),
+            Self::MsgPack => MsgPackCodec.encode(value),
+        }
+    }
+
+    fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T, MessagingError> {
+        match self {
+            Self::Json => JsonCodec.decode(data),
+            Self::Cbor => CborCodec.decode(data),
+            Self::MsgPack => MsgPackCodec.decode(data),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::error::CodecDirection;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Event {
+        id: u32,
+        name: String,
+    }
+
+    #[test]
+    fn from_content_type_ignores_parameters_and_case() {
+        let cases = [
+            ("application/json", Some(Encoding::Json)),
+            ("application/json; charset=utf-8", Some(Encoding::Json)),
+            ("Application/JSON ;charset=utf-8", Some(Encoding::Json)),
+            (" application/cbor ", Some(Encoding::Cbor)),
+            ("application/msgpack; v=5", Some(Encoding::MsgPack)),
+            ("text/plain; charset=utf-8", None),
+            ("", None),
+        ];
+        for (content_type, expected) in cases {
+            assert_eq!(
+                Encoding::from_content_type(content_type),
+                expected,
+                "{content_type:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn from_attrs_defaults_to_json_and_rejects_unknown_types() {
+        assert_eq!(
+            Encoding::from_attrs(&HashMap::new()).unwrap(),
+            Encoding::Json
+        );
+
+        let cbor = HashMap::from([(
+            HEADER_CONTENT_TYPE.to_string(),
+            "application/cbor; foo=bar".to_string(),
+        )]);
+        assert_eq!(Encoding::from_attrs(&cbor).unwrap(), Encoding::Cbor);
+
+        let xml = HashMap::from([(HEADER_CONTENT_TYPE.to_string(), "text/xml".to_string())]);
+        assert!(matches!(
+            Encoding::from_attrs(&xml),
+            Err(MessagingError::Codec {
+                direction: CodecDirection::Decode,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn config_names_parse() {
+        assert_eq!("JSON".parse::<Encoding>().unwrap(), Encoding::Json);
+        assert_eq!(
+            "messagepack".parse::<Encoding>().unwrap(),
+            Encoding::MsgPack
+        );
+        assert_eq!(
+            "application/cbor".parse::<Encoding>().unwrap(),
+            Encoding::Cbor
+        );
+        assert!("yaml".parse::<Encoding>().is_err());
+    }
+
+    #[test]
+    fn every_encoding_round_trips() {
+        let event = Event {
+            id: 7,
+            name: "created".to_string(),
+        };
+        for encoding in [Encoding::Json, Encoding::Cbor, Encoding::MsgPack] {
+            let bytes = encoding.encode(&event).unwrap();
+            assert_eq!(
+                encoding.decode::<Event>(&bytes).unwrap(),
+                event,
+                "{encoding:?}"
+            );
+            assert_eq!(
+                Encoding::from_content_type(encoding.content_type()),
+                Some(encoding)
+            );
+        }
+    }
+
+    #[test]
+    fn undecodable_payload_is_a_decode_error() {
+        let err = Encoding::Json.decode::<Event>(b"not json").unwrap_err();
+        assert!(matches!(
+            err,
+            MessagingError::Codec {
+                direction: CodecDirection::Decode,
+                ..
+            }
+        ));
+        assert!(!err.is_retryable());
+    }
+}
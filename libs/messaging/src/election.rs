@@ -0,0 +1,187 @@
+use std::{future::Future, sync::Arc, time::Duration};
+
+use tokio::{
+    sync::{Notify, watch},
+    task::JoinHandle,
+};
+
+use crate::{
+    KeyValueStore,
+    lock::{DistributedLock, Lease},
+};
+
+/// Keeps one of several replicas leader for `election`.
+///
+/// Every candidate runs the same loop: the leader renews its lease every
+/// third of the TTL, the others try to take it. Leadership therefore moves
+/// within one TTL when the leader dies, and at once when it calls
+/// `LeaderHandle::resign`. A leader that cannot renew steps down when its
+/// lease expires by its own clock.
+///
+/// ```rust,ignore
+/// let leader = LeaderElector::new(kv, "outbox-relay", instance_id, Duration::from_secs(15)).start();
+/// let mut is_leader = leader.is_leader();
+/// while is_leader.changed().await.is_ok() {
+///     if *is_leader.borrow() { /* start the job */ } else { /* stop it */ }
+/// }
+/// leader.resign().await;
+/// ```
+#[derive(Debug)]
+pub struct LeaderElector {
+    lock: DistributedLock,
+    election: String,
+}
+
+/// A running election; see `LeaderElector`.
+#[derive(Debug)]
+pub struct LeaderHandle {
+    is_leader: watch::Receiver<bool>,
+    stop: Arc<Notify>,
+    task: JoinHandle<()>,
+}
+
+impl LeaderElector {
+    /// `candidate` must be unique per replica.
+    pub fn new(
+        store: Arc<dyn KeyValueStore>,
+        election: impl Into<String>,
+        candidate: impl Into<String>,
+        ttl: Duration,
+    ) -> Self {
+        let election = election.into();
+        Self {
+            lock: DistributedLock::new(store, format!("leader.{election}"), candidate, ttl),
+            election,
+        }
+    }
+
+    /// Start campaigning in the background.
+    pub fn start(self) -> LeaderHandle {
+        let (tx, is_leader) = watch::channel(false);
+        let stop = Arc::new(Notify::new());
+        let stopped = Arc::clone(&stop);
+        let task = tokio::spawn(self.campaign(tx, stopped));
+        LeaderHandle {
+            is_leader,
+            stop,
+            task,
+        }
+    }
+
+    async fn campaign(self, tx: watch::Sender<bool>, stop: Arc<Notify>) {
+        let election = self.election.as_str();
+        let candidate = self.lock.holder().to_string();
+        let interval = self.lock.ttl() / 3;
+        let mut lease: Option<Lease> = None;
+
+        loop {
+            lease = match lease.take() {
+                // Renewals kept failing until the lease ran out; another
+                // candidate may hold it by now.
+                Some(held) if held.expires_at <= chrono::Utc::now() => {
+                    tracing::warn!(election, candidate, "leader: lease expired");
+                    None
+                }
+                Some(held) => match self.lock.renew(&held).await {
+                    Ok(renewed) => Some(renewed),
+                    // Keep the old lease on transient errors; it may still
+                    // be valid and renewing can succeed next time.
+                    Err(e) if e.is_retryable() && held.expires_at > chrono::Utc::now() => {
+                        tracing::warn!(election, error = %e, "leader: renew failed");
+                        Some(held)
+                    }
+                    Err(e) => {
+                        tracing::warn!(election, candidate, error = %e, "leader: lost leadership");
+                        None
+                    }
+                },
+                None => match self.lock.try_acquire().await {
+                    Ok(Some(acquired)) => {
+                        tracing::info!(election, candidate, "leader: elected");
+                        Some(acquired)
+                    }
+                    Ok(None) => None,
+                    Err(e) => {
+                        tracing::warn!(election, error = %e, "leader: campaign failed");
+                        None
+                    }
+                },
+            };
+            tx.send_if_modified(|leading| {
+                let now = lease.is_some();
+                std::mem::replace(leading, now) != now
+            });
+
+            // After a failed renew, wake up no later than the lease expiry
+            // so leadership is dropped before another replica may take it.
+            let wait = lease
+                .as_ref()
+                .and_then(|held| (held.expires_at - chrono::Utc::now()).to_std().ok())
+                .map_or(interval, |left| left.min(interval));
+
+            tokio::select! {
+                _ = stop.notified() => break,
+                _ = tokio::time::sleep(wait) => {}
+            }
+        }
+
+        // Hand off: drop leadership first so the job stops, then free the
+        // key so another candidate takes over without waiting for expiry.
+        tx.send_replace(false);
+        if let Some(held) = lease {
+            match self.lock.release(held).await {
+                Ok(()) => tracing::info!(election, candidate, "leader: resigned"),
+                Err(e) => tracing::warn!(election, error = %e, "leader: release failed"),
+            }
+        }
+    }
+}
+
+impl LeaderHandle {
+    /// `true` while this replica leads; `changed()` fires on transitions.
+    pub fn is_leader(&self) -> watch::Receiver<bool> {
+        self.is_leader.clone()
+    }
+
+    /// Run `job` only while leading: it is started on election and aborted
+    /// when leadership is lost or resigned. Abort the returned task to stop;
+    /// a running job is aborted with it.
+    pub fn run_while_leader<F, Fut>(&self, job: F) -> JoinHandle<()>
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let mut is_leader = self.is_leader();
+        tokio::spawn(async move {
+            // Dropped (and the job aborted) on step-down and when this task
+            // ends or is aborted.
+            let mut running: Option<AbortOnDrop> = None;
+            loop {
+                let leading = *is_leader.borrow_and_update();
+                if !leading {
+                    running = None;
+                } else if running.is_none() {
+                    running = Some(AbortOnDrop(tokio::spawn(job())));
+                }
+                if is_leader.changed().await.is_err() {
+                    break;
+                }
+            }
+        })
+    }
+
+    /// Stop campaigning and release leadership if held.
+    pub async fn resign(self) {
+        self.stop.notify_one();
+        let _ = self.task.await;
+    }
+}
+
+/// Aborts the wrapped task when dropped.
+struct AbortOnDrop(JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
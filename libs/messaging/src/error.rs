@@ -1,36 +1,249 @@
+use std::{fmt, time::Duration};
+
+use async_nats::{
+    ConnectError, PublishError, RequestError, RequestErrorKind, SubscribeError, client::FlushError,
+};
 use thiserror::Error;
 
+/// Boxed source error carried by `MessagingError` variants.
+pub type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
 #[derive(Debug, Error)]
 pub enum MessagingError {
-    /// Failure during message publish
-    #[error("Publish failed: {0}")]
-    Publish(String),
+    /// Nobody is subscribed to the request subject
+    #[error("No responders for {subject}")]
+    NoResponders { subject: String },
 
-    /// Failure creating/maintaining a subscriber
-    #[error("Subscribe failed: {0}")]
-    Subscribe(String),
+    /// A request got no reply, or a handler ran past its deadline
+    #[error("Timed out after {0:?}")]
+    Timeout(Duration),
 
-    /// Failure removing a subscription
-    #[error("Unsubscribe failed: {0}")]
-    Unsubscribe(String),
+    /// Transport failure talking to the server (connect, publish,
+    /// subscribe, ack, ...) or invalid client-side settings
+    #[error("{op} failed: {source}")]
+    Io {
+        op: IoOp,
+        #[source]
+        source: BoxError,
+    },
 
-    /// Request/reply pattern failure (includes timeout)
-    #[error("Request failed: {0}")]
-    Request(String),
+    /// Payload or header could not be encoded / decoded
+    #[error("{direction} failed: {source}")]
+    Codec {
+        direction: CodecDirection,
+        #[source]
+        source: BoxError,
+    },
 
-    /// Could not serialize outbound payload
-    #[error("Serialization failed: {0}")]
-    Serialization(String),
+    /// Handler (or a store it relies on) returned an error
+    #[error("Handler error: {source}")]
+    Handler {
+        #[source]
+        source: BoxError,
+    },
 
-    /// Could not deserialize inbound payload
-    #[error("Deserialization failed: {0}")]
-    Deserialization(String),
+    /// A conditional write lost: the key exists or has a newer revision
+    #[error("Write conflict on {key}")]
+    Conflict { key: String },
 
-    /// Handler returned an error
-    #[error("Handler error: {0}")]
-    Handler(String),
+    /// Structured error reply (`Nats-Service-Error` / `-Code` headers)
+    #[error("Service error {code}: {description}")]
+    Service { code: u16, description: String },
 
     /// Connection has been closed / drained
     #[error("Connection closed")]
     Closed,
 }
+
+/// Which transport operation an `Io` error came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoOp {
+    Connect,
+    Publish,
+    Subscribe,
+    Unsubscribe,
+    Request,
+    Ack,
+    Flush,
+    Store,
+}
+
+impl fmt::Display for IoOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Connect => "Connect",
+            Self::Publish => "Publish",
+            Self::Subscribe => "Subscribe",
+            Self::Unsubscribe => "Unsubscribe",
+            Self::Request => "Request",
+            Self::Ack => "Ack",
+            Self::Flush => "Flush",
+            Self::Store => "Store",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodecDirection {
+    Encode,
+    Decode,
+}
+
+impl fmt::Display for CodecDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Encode => "Serialization",
+            Self::Decode => "Deserialization",
+        })
+    }
+}
+
+impl MessagingError {
+    /// Build a structured error reply, e.g. `MessagingError::service(404, "user not found")`.
+    pub fn service(code: u16, description: impl Into<String>) -> Self {
+        Self::Service {
+            code,
+            description: description.into(),
+        }
+    }
+
+    pub fn io(op: IoOp, source: impl Into<BoxError>) -> Self {
+        Self::Io {
+            op,
+            source: source.into(),
+        }
+    }
+
+    /// Outbound payload / header could not be encoded.
+    pub fn encode(source: impl Into<BoxError>) -> Self {
+        Self::Codec {
+            direction: CodecDirection::Encode,
+            source: source.into(),
+        }
+    }
+
+    /// Inbound payload / header could not be decoded.
+    pub fn decode(source: impl Into<BoxError>) -> Self {
+        Self::Codec {
+            direction: CodecDirection::Decode,
+            source: source.into(),
+        }
+    }
+
+    pub fn handler(source: impl Into<BoxError>) -> Self {
+        Self::Handler {
+            source: source.into(),
+        }
+    }
+
+    /// Map an async-nats request error; `subject` and `timeout` fill in
+    /// what the client error does not carry.
+    pub fn from_request(err: RequestError, subject: &str, timeout: Duration) -> Self {
+        match err.kind() {
+            RequestErrorKind::NoResponders => Self::NoResponders {
+                subject: subject.to_string(),
+            },
+            RequestErrorKind::TimedOut => Self::Timeout(timeout),
+            RequestErrorKind::Other => Self::io(IoOp::Request, err),
+        }
+    }
+
+    /// Whether trying the same operation again may succeed.
+    ///
+    /// Transport failures, timeouts, missing responders, handler errors and
+    /// 5xx service errors are transient; codec errors, write conflicts, 4xx
+    /// service errors and a closed connection are not, so retrying them only delays the
+    /// dead letter.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::NoResponders { .. } | Self::Timeout(_) | Self::Io { .. } => true,
+            Self::Handler { source } => source
+                .downcast_ref::<MessagingError>()
+                .is_none_or(MessagingError::is_retryable),
+            Self::Service { code, .. } => *code >= 500,
+            Self::Codec { .. } | Self::Conflict { .. } | Self::Closed => false,
+        }
+    }
+}
+
+impl From<ConnectError> for MessagingError {
+    fn from(err: ConnectError) -> Self {
+        Self::io(IoOp::Connect, err)
+    }
+}
+
+impl From<PublishError> for MessagingError {
+    fn from(err: PublishError) -> Self {
+        Self::io(IoOp::Publish, err)
+    }
+}
+
+impl From<SubscribeError> for MessagingError {
+    fn from(err: SubscribeError) -> Self {
+        Self::io(IoOp::Subscribe, err)
+    }
+}
+
+impl From<FlushError> for MessagingError {
+    fn from(err: FlushError) -> Self {
+        Self::io(IoOp::Flush, err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transient_errors_are_retryable() {
+        let retryable = [
+            MessagingError::NoResponders {
+                subject: "user.get".to_string(),
+            },
+            MessagingError::Timeout(Duration::from_secs(1)),
+            MessagingError::io(IoOp::Publish, "connection reset"),
+            MessagingError::handler("database unavailable"),
+            MessagingError::service(500, "internal"),
+            MessagingError::service(503, "busy"),
+        ];
+        for err in retryable {
+            assert!(err.is_retryable(), "{err:?}");
+        }
+    }
+
+    #[test]
+    fn permanent_errors_are_not_retryable() {
+        let permanent = [
+            MessagingError::encode("bad value"),
+            MessagingError::decode("bad payload"),
+            MessagingError::Conflict {
+                key: "leader.outbox".to_string(),
+            },
+            MessagingError::service(400, "bad request"),
+            MessagingError::service(499, "client closed"),
+            MessagingError::Closed,
+        ];
+        for err in permanent {
+            assert!(!err.is_retryable(), "{err:?}");
+        }
+    }
+
+    #[test]
+    fn handler_error_wrapping_a_messaging_error_follows_it() {
+        assert!(!MessagingError::handler(MessagingError::decode("bad payload")).is_retryable());
+        assert!(!MessagingError::handler(MessagingError::service(404, "not found")).is_retryable());
+        assert!(MessagingError::handler(MessagingError::Timeout(Duration::ZERO)).is_retryable());
+    }
+
+    #[test]
+    fn display_names_the_operation() {
+        assert_eq!(
+            MessagingError::io(IoOp::Subscribe, "denied").to_string(),
+            "Subscribe failed: denied"
+        );
+        assert_eq!(
+            MessagingError::decode("eof").to_string(),
+            "Deserialization failed: eof"
+        );
+    }
+}
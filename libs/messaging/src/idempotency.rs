@@ -0,0 +1,38 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::MessagingError;
+
+/// Outcome of `IdempotencyStore::try_claim`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Claim {
+    /// The caller now processes the message.
+    Acquired,
+    /// Another delivery holds an unexpired processing lease.
+    InProgress,
+    /// The message was already processed.
+    Done,
+}
+
+/// Records which message ids a consumer has already handled.
+///
+/// Used by `idempotency_middleware` to skip redelivered and duplicate
+/// messages. Keys are opaque strings built by the middleware
+/// (`<scope>:<subject>:<message id>`).
+///
+/// A key is first claimed for processing with a short `lease` and only
+/// counts as a duplicate once `complete` marks it done, so a consumer that
+/// crashes mid-message does not make its redelivery look handled.
+#[async_trait]
+pub trait IdempotencyStore: Send + Sync + std::fmt::Debug {
+    /// Claim `key` for processing for `lease`. A key whose lease expired
+    /// is claimed again.
+    async fn try_claim(&self, key: &str, lease: Duration) -> Result<Claim, MessagingError>;
+
+    /// Mark `key` processed; it is remembered for `ttl`.
+    async fn complete(&self, key: &str, ttl: Duration) -> Result<(), MessagingError>;
+
+    /// Drop a claim so a redelivery of the message is processed again.
+    async fn release(&self, key: &str) -> Result<(), MessagingError>;
+}
@@ -0,0 +1,129 @@
+use std::{fmt::Debug, time::Duration};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures_util::stream::BoxStream;
+
+use crate::MessagingError;
+
+/// Settings for a key-value or object-store bucket, applied when the
+/// bucket is first created.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BucketOptions {
+    pub bucket: String,
+    /// Revisions kept per key (key-value only, 1..=64).
+    pub history: i64,
+    /// Drop entries older than this; `None` keeps them forever.
+    pub max_age: Option<Duration>,
+    /// Allow per-key TTLs (`KeyValueStore::create_with_ttl`); needs a
+    /// NATS 2.11+ server.
+    pub per_key_ttl: bool,
+    /// Keep data in memory instead of on disk.
+    pub in_memory: bool,
+    pub replicas: usize,
+}
+
+impl BucketOptions {
+    pub fn new(bucket: impl Into<String>) -> Self {
+        Self {
+            bucket: bucket.into(),
+            history: 1,
+            max_age: None,
+            per_key_ttl: false,
+            in_memory: false,
+            replicas: 1,
+        }
+    }
+
+    pub fn with_history(mut self, history: i64) -> Self {
+        self.history = history;
+        self
+    }
+
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    pub fn with_per_key_ttl(mut self) -> Self {
+        self.per_key_ttl = true;
+        self
+    }
+
+    pub fn in_memory(mut self) -> Self {
+        self.in_memory = true;
+        self
+    }
+
+    pub fn with_replicas(mut self, replicas: usize) -> Self {
+        self.replicas = replicas;
+        self
+    }
+}
+
+/// What produced a `KvEntry`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KvOperation {
+    Put,
+    Delete,
+    Purge,
+}
+
+/// One revision of a key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KvEntry {
+    pub key: String,
+    /// Empty for deletes and purges.
+    pub value: Bytes,
+    /// Bucket-wide sequence; pass it to `update` for optimistic locking.
+    pub revision: u64,
+    pub created: DateTime<Utc>,
+    pub operation: KvOperation,
+}
+
+/// Changes to the watched keys, starting with their current values.
+pub type KvWatch = BoxStream<'static, Result<KvEntry, MessagingError>>;
+
+/// A key-value bucket.
+///
+/// Keys are dot-separated like subjects; `watch` accepts the same
+/// wildcards (`*` for one token, `>` for the rest). Writes return the new
+/// revision. `create` and `update` fail with `MessagingError::Conflict`
+/// when the key already exists or has moved past `revision`.
+#[async_trait]
+pub trait KeyValueStore: Send + Sync + Debug {
+    /// Latest value of `key`; `None` if it was never set, or was deleted
+    /// or expired.
+    async fn get(&self, key: &str) -> Result<Option<KvEntry>, MessagingError>;
+
+    async fn put(&self, key: &str, value: Bytes) -> Result<u64, MessagingError>;
+
+    /// Set `key` only if it does not exist.
+    async fn create(&self, key: &str, value: Bytes) -> Result<u64, MessagingError>;
+
+    /// Like `create`; the key expires after `ttl` unless rewritten.
+    /// Needs `BucketOptions::per_key_ttl`.
+    async fn create_with_ttl(
+        &self,
+        key: &str,
+        value: Bytes,
+        ttl: Duration,
+    ) -> Result<u64, MessagingError>;
+
+    /// Set `key` only if its latest revision is still `revision`.
+    async fn update(&self, key: &str, value: Bytes, revision: u64) -> Result<u64, MessagingError>;
+
+    /// Delete `key`, only if its latest revision is `revision` when given.
+    /// History is kept; use `purge` to drop it.
+    async fn delete(&self, key: &str, revision: Option<u64>) -> Result<(), MessagingError>;
+
+    /// Delete `key` and its history.
+    async fn purge(&self, key: &str) -> Result<(), MessagingError>;
+
+    /// Keys that currently hold a value.
+    async fn keys(&self) -> Result<Vec<String>, MessagingError>;
+
+    /// Stream changes to keys matching `pattern` (e.g. `"config.>"`).
+    async fn watch(&self, pattern: &str) -> Result<KvWatch, MessagingError>;
+}
@@ -1,9 +1,40 @@
+pub mod ack;
+pub mod cloudevent;
+pub mod codec;
+pub mod election;
 pub mod error;
+pub mod idempotency;
+pub mod kv;
+pub mod lock;
+pub mod memory;
 pub mod message;
 pub mod nats;
+pub mod object_store;
+pub mod partition;
+pub mod request;
+pub mod rpc;
+pub mod scheduler;
+mod subscription;
+pub mod topic;
 pub mod traits;
 
+pub use ack::AckKind;
+pub use cloudevent::{CloudEvent, CloudEventMode};
+pub use codec::{Codec, Encoding};
+pub use election::{LeaderElector, LeaderHandle};
 pub use error::MessagingError;
+pub use idempotency::{Claim, IdempotencyStore};
+pub use kv::{BucketOptions, KeyValueStore, KvEntry, KvOperation};
+pub use lock::{DistributedLock, Lease};
 pub use message::Message;
-pub use traits::{Broker, Client, Handler, Publisher, QueueClient, QueueSubscriber, Subscriber};
+pub use object_store::{ObjectInfo, ObjectMeta, ObjectStore};
+pub use partition::{PartitionHandle, PartitionedConsumer};
+pub use request::{ReplyStream, RequestMany, stream_handler};
+pub use scheduler::ScheduledPublisher;
+pub use subscription::{SubscribeOptions, SubscriptionId};
+pub use topic::Topic;
+pub use traits::{
+    Broker, Client, DurableSubscriber, DurableSubscriberExt, Handler, Publisher, PublisherExt,
+    QueueClient, QueueSubscriber, QueueSubscriberExt, Subscriber, SubscriberExt,
+};
 pub use traits::{handler, reply_handler};
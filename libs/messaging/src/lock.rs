@@ -0,0 +1,139 @@
+//! Lease-based distributed lock over a `KeyValueStore`.
+//!
+//! The lock key holds `{holder, expires_at}`. Taking it is a compare-and-set
+//! (`create` when free, `update` at the seen revision when the previous
+//! lease has expired), so of several contenders exactly one wins. Holders
+//! renew before `expires_at`; a holder that dies simply stops renewing and
+//! the lease can be taken over once it expires.
+//!
+//! Expiry is judged by the contenders' clocks, so the TTL must be well
+//! above the clock skew between replicas. Giving the bucket a `max_age` of
+//! the TTL (`BucketOptions::with_max_age`) also clears stale keys.
+
+use std::{sync::Arc, time::Duration};
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{KeyValueStore, MessagingError};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LeaseRecord {
+    holder: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// A named lock contended by holders with distinct ids.
+#[derive(Debug, Clone)]
+pub struct DistributedLock {
+    store: Arc<dyn KeyValueStore>,
+    key: String,
+    holder: String,
+    ttl: Duration,
+}
+
+/// Proof of holding a `DistributedLock` until `expires_at`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lease {
+    pub key: String,
+    pub holder: String,
+    pub expires_at: DateTime<Utc>,
+    revision: u64,
+}
+
+impl DistributedLock {
+    /// `holder` must be unique per contender (e.g. service name + instance id).
+    pub fn new(
+        store: Arc<dyn KeyValueStore>,
+        key: impl Into<String>,
+        holder: impl Into<String>,
+        ttl: Duration,
+    ) -> Self {
+        Self {
+            store,
+            key: key.into(),
+            holder: holder.into(),
+            ttl,
+        }
+    }
+
+    pub fn holder(&self) -> &str {
+        &self.holder
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Take the lock if it is free, expired or already ours; `None` while
+    /// someone else holds it.
+    pub async fn try_acquire(&self) -> Result<Option<Lease>, MessagingError> {
+        let (value, expires_at) = self.record()?;
+        let written = match self.store.get(&self.key).await? {
+            None => self.store.create(&self.key, value).await,
+            Some(entry) => {
+                let current: Option<LeaseRecord> = serde_json::from_slice(&entry.value).ok();
+                let takeable =
+                    current.is_none_or(|c| c.holder == self.holder || c.expires_at <= Utc::now());
+                if !takeable {
+                    return Ok(None);
+                }
+                self.store.update(&self.key, value, entry.revision).await
+            }
+        };
+
+        match written {
+            Ok(revision) => Ok(Some(self.lease(revision, expires_at))),
+            // Another contender got there first.
+            Err(MessagingError::Conflict { .. }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Wait until the lock is taken, polling every `poll`.
+    pub async fn acquire(&self, poll: Duration) -> Result<Lease, MessagingError> {
+        loop {
+            if let Some(lease) = self.try_acquire().await? {
+                return Ok(lease);
+            }
+            tokio::time::sleep(poll).await;
+        }
+    }
+
+    /// Extend `lease` by the TTL. Fails with `MessagingError::Conflict` if
+    /// the lease was lost (expired and taken over).
+    pub async fn renew(&self, lease: &Lease) -> Result<Lease, MessagingError> {
+        let (value, expires_at) = self.record()?;
+        let revision = self.store.update(&self.key, value, lease.revision).await?;
+        Ok(self.lease(revision, expires_at))
+    }
+
+    /// Give the lock up so another contender can take it at once. A lease
+    /// that was already lost is left alone.
+    pub async fn release(&self, lease: Lease) -> Result<(), MessagingError> {
+        match self.store.delete(&self.key, Some(lease.revision)).await {
+            Ok(()) | Err(MessagingError::Conflict { .. }) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn record(&self) -> Result<(Bytes, DateTime<Utc>), MessagingError> {
+        let expires_at = Utc::now() + self.ttl;
+        let record = LeaseRecord {
+            holder: self.holder.clone(),
+            expires_at,
+        };
+        let value = serde_json::to_vec(&record).map_err(MessagingError::encode)?;
+        Ok((Bytes::from(value), expires_at))
+    }
+
+    fn lease(&self, revision: u64, expires_at: DateTime<Utc>) -> Lease {
+        Lease {
+            key: self.key.clone(),
+            holder: self.holder.clone(),
+            expires_at,
+            revision,
+        }
+    }
+}
@@ -0,0 +1,590 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures_util::{StreamExt, stream::BoxStream};
+use tokio::sync::mpsc;
+
+use ro_common::id::generate_nanoid;
+use ro_config::config::nats::NatsConfig;
+
+use crate::{
+    Broker, Encoding, Handler, MessagingError, Publisher, QueueSubscriber, SubscribeOptions,
+    Subscriber, SubscriptionId,
+    memory::subject,
+    nats::{
+        factory::MessageFactory,
+        middleware::{MiddlewareFn, NatsHandlerFn, apply_middleware},
+    },
+    request::{ReplyStream, RequestMany, gather, reply_stream},
+    subscription::{Delivery, Subscription, spawn_drain, wrap_handler},
+};
+
+/// In-process broker with NATS semantics, for tests and local development.
+///
+/// Messages go through the same `MessageFactory` (headers, `base_path`) and
+/// middleware chain as `NatsClient`, so handlers behave identically:
+///   - `*` / `>` subject wildcards
+///   - queue groups deliver each message to one member, round-robin
+///   - reply handlers answer `Broker::request`
+#[derive(Debug, Clone)]
+pub struct MemoryBroker {
+    inner: Arc<Inner>,
+    /// Flow control for subscriptions made through this handle
+    subscribe_options: SubscribeOptions,
+}
+
+#[derive(Debug)]
+struct Inner {
+    factory: Arc<MessageFactory>,
+    middlewares: Vec<MiddlewareFn>,
+    routes: Mutex<Routes>,
+    closed: AtomicBool,
+    drain_timeout: Duration,
+}
+
+#[derive(Debug, Default)]
+struct Routes {
+    subs: Vec<Route>,
+    /// queue group → round-robin cursor
+    cursors: HashMap<String, usize>,
+}
+
+#[derive(Debug)]
+struct Route {
+    id: SubscriptionId,
+    /// Resolved subject pattern (base_path applied)
+    subject: String,
+    group: Option<String>,
+    tx: mpsc::UnboundedSender<async_nats::Message>,
+    /// `None` for request inboxes, which are read directly.
+    drain: Option<Subscription>,
+}
+
+/// Removes a request inbox route when its replies are no longer read.
+struct InboxGuard {
+    inner: Arc<Inner>,
+    subject: String,
+}
+
+impl Drop for InboxGuard {
+    fn drop(&mut self) {
+        if let Ok(mut routes) = self.inner.routes.lock() {
+            routes.subs.retain(|route| route.subject != self.subject);
+        }
+    }
+}
+
+impl MemoryBroker {
+    pub fn new(name: impl Into<String>, cfg: NatsConfig, middlewares: Vec<MiddlewareFn>) -> Self {
+        Self {
+            subscribe_options: SubscribeOptions::from_config(&cfg),
+            inner: Arc::new(Inner {
+                drain_timeout: cfg.drain_timeout(),
+                factory: Arc::new(MessageFactory::new(name.into(), Arc::new(cfg))),
+                middlewares,
+                routes: Mutex::new(Routes::default()),
+                closed: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    /// A handle on the same broker whose subscriptions use `options`.
+    pub fn with_subscribe_options(&self, options: SubscribeOptions) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+            subscribe_options: options,
+        }
+    }
+
+    /// Message factory (subjects, headers, CloudEvents) for this broker.
+    pub fn factory(&self) -> &MessageFactory {
+        &self.inner.factory
+    }
+
+    fn ensure_open(&self) -> Result<(), MessagingError> {
+        if self.inner.closed.load(Ordering::Acquire) {
+            return Err(MessagingError::Closed);
+        }
+        Ok(())
+    }
+
+    /// Hand `msg` to every matching subscriber (one member per queue group).
+    /// Returns the number of subscriptions that received it.
+    fn route(inner: &Inner, msg: async_nats::Message) -> usize {
+        let Ok(mut routes) = inner.routes.lock() else {
+            return 0;
+        };
+
+        let mut targets = Vec::new();
+        let mut groups: Vec<(String, Vec<usize>)> = Vec::new();
+        for (idx, route) in routes.subs.iter().enumerate() {
+            if !subject::matches(&route.subject, msg.subject.as_str()) {
+                continue;
+            }
+            match &route.group {
+                None => targets.push(idx),
+                Some(group) => match groups.iter_mut().find(|(g, _)| g == group) {
+                    Some((_, members)) => members.push(idx),
+                    None => groups.push((group.clone(), vec![idx])),
+                },
+            }
+        }
+
+        for (group, members) in groups {
+            let cursor = routes.cursors.entry(group).or_default();
+            targets.push(members[*cursor % members.len()]);
+            *cursor = cursor.wrapping_add(1);
+        }
+
+        targets
+            .into_iter()
+            .filter(|&idx| routes.subs[idx].tx.send(msg.clone()).is_ok())
+            .count()
+    }
+
+    /// Route a request whose replies go to a fresh inbox. The inbox is
+    /// removed when the returned stream is dropped.
+    fn send_to_inbox<T: serde::Serialize>(
+        &self,
+        topic: &str,
+        payload: &T,
+        mut attrs: HashMap<String, String>,
+    ) -> Result<BoxStream<'static, async_nats::Message>, MessagingError> {
+        self.ensure_open()?;
+        let data = self.inner.factory.encode(payload, &mut attrs)?;
+        let mut msg = self.inner.factory.build_msg(topic, None, data, attrs)?;
+
+        let inbox = format!("_INBOX.{}", generate_nanoid());
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.add_route(inbox.clone(), None, tx, None);
+        msg.reply = Some(inbox.clone().into());
+        let guard = InboxGuard {
+            inner: Arc::clone(&self.inner),
+            subject: inbox,
+        };
+
+        if Self::route(&self.inner, msg) == 0 {
+            return Err(MessagingError::NoResponders {
+                subject: topic.to_string(),
+            });
+        }
+        Ok(
+            futures_util::stream::unfold((rx, guard), |(mut rx, guard)| async move {
+                rx.recv().await.map(|reply| (reply, (rx, guard)))
+            })
+            .boxed(),
+        )
+    }
+
+    fn add_route(
+        &self,
+        subject: String,
+        group: Option<String>,
+        tx: mpsc::UnboundedSender<async_nats::Message>,
+        drain: Option<Subscription>,
+    ) -> SubscriptionId {
+        let id = SubscriptionId::next();
+        if let Ok(mut routes) = self.inner.routes.lock() {
+            routes.subs.push(Route {
+                id,
+                subject,
+                group,
+                tx,
+                drain,
+            });
+        }
+        id
+    }
+
+    async fn add_subscription(
+        &self,
+        operation: &str,
+        topic: &str,
+        group: Option<String>,
+        handler: Handler,
+    ) -> Result<SubscriptionId, MessagingError> {
+        self.ensure_open()?;
+        let subject = self.inner.factory.subject(topic);
+
+        let inner = Arc::clone(&self.inner);
+        let publish_reply: NatsHandlerFn = Arc::new(move |reply: async_nats::Message| {
+            let inner = Arc::clone(&inner);
+            Box::pin(async move {
+                Self::route(&inner, reply);
+                Ok(())
+            })
+        });
+
+        let transport_handler = apply_middleware(
+            operation,
+            wrap_handler(Arc::clone(&self.inner.factory), handler, publish_reply),
+            &self.inner.middlewares,
+        );
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let deliveries = futures_util::stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|msg| (Delivery::from(msg), rx))
+        })
+        .boxed();
+
+        let drain = spawn_drain(
+            subject.clone(),
+            deliveries,
+            transport_handler,
+            self.subscribe_options,
+        );
+        Ok(self.add_route(subject, group, tx, Some(drain)))
+    }
+
+    /// Remove matching routes and gracefully drain their subscriptions.
+    ///
+    /// Dropping a route's sender ends its stream; running handlers get
+    /// `NatsConfig::drain_timeout` to finish.
+    async fn remove_routes(&self, pred: impl Fn(&Route) -> bool) {
+        let removed: Vec<Route> = match self.inner.routes.lock() {
+            Ok(mut routes) => {
+                let (removed, kept) = std::mem::take(&mut routes.subs)
+                    .into_iter()
+                    .partition(|route| pred(route));
+                routes.subs = kept;
+                removed
+            }
+            Err(_) => return,
+        };
+
+        let deadline = self.inner.drain_timeout;
+        futures_util::future::join_all(removed.into_iter().filter_map(|route| {
+            let Route { subject, drain, .. } = route;
+            drain.map(|sub| async move { sub.drain(&subject, deadline).await })
+        }))
+        .await;
+    }
+}
+
+#[async_trait]
+impl Publisher for MemoryBroker {
+    async fn publish(
+        &self,
+        topic: &str,
+        data: Bytes,
+        attrs: HashMap<String, String>,
+    ) -> Result<(), MessagingError> {
+        self.ensure_open()?;
+        let msg = self.inner.factory.build_msg(topic, None, data, attrs)?;
+
+        let inner = Arc::clone(&self.inner);
+        let pub_fn: NatsHandlerFn = Arc::new(move |msg: async_nats::Message| {
+            let inner = Arc::clone(&inner);
+            Box::pin(async move {
+                Self::route(&inner, msg);
+                Ok(())
+            })
+        });
+
+        let chained = apply_middleware("publish", pub_fn, &self.inner.middlewares);
+        chained(msg).await
+    }
+
+    /// Gracefully stop every subscription; later calls fail with
+    /// `MessagingError::Closed`.
+    async fn close(&self) -> Result<(), MessagingError> {
+        self.inner.closed.store(true, Ordering::Release);
+        self.remove_routes(|_| true).await;
+        Ok(())
+    }
+
+    fn encoding(&self) -> Encoding {
+        self.inner.factory.encoding()
+    }
+}
+
+#[async_trait]
+impl Subscriber for MemoryBroker {
+    async fn subscribe(
+        &self,
+        topic: &str,
+        handler: Handler,
+    ) -> Result<SubscriptionId, MessagingError> {
+        self.add_subscription("subscriber", topic, None, handler)
+            .await
+    }
+
+    async fn unsubscribe(&self, topic: &str) -> Result<(), MessagingError> {
+        let subject = self.inner.factory.subject(topic);
+        self.remove_routes(|route| route.drain.is_some() && route.subject == subject)
+            .await;
+        Ok(())
+    }
+
+    async fn unsubscribe_id(&self, id: SubscriptionId) -> Result<(), MessagingError> {
+        self.remove_routes(|route| route.drain.is_some() && route.id == id)
+            .await;
+        Ok(())
+    }
+
+    async fn close(&self) -> Result<(), MessagingError> {
+        Publisher::close(self).await
+    }
+}
+
+#[async_trait]
+impl QueueSubscriber for MemoryBroker {
+    async fn queue_subscribe(
+        &self,
+        topic: &str,
+        group: &str,
+        handler: Handler,
+    ) -> Result<SubscriptionId, MessagingError> {
+        self.add_subscription("queue_subscribe", topic, Some(group.to_string()), handler)
+            .await
+    }
+
+    async fn unsubscribe(&self, topic: &str) -> Result<(), MessagingError> {
+        Subscriber::unsubscribe(self, topic).await
+    }
+
+    async fn close(&self) -> Result<(), MessagingError> {
+        Publisher::close(self).await
+    }
+}
+
+#[async_trait]
+impl Broker for MemoryBroker {
+    async fn request<T, R>(
+        &self,
+        topic: &str,
+        payload: &T,
+        attrs: HashMap<String, String>,
+        timeout: Duration,
+    ) -> Result<R, MessagingError>
+    where
+        T: serde::Serialize + Send + Sync,
+        R: serde::de::DeserializeOwned,
+    {
+        let mut replies = self.send_to_inbox(topic, payload, attrs)?;
+        let reply = tokio::time::timeout(timeout, replies.next())
+            .await
+            .map_err(|_| MessagingError::Timeout(timeout))?
+            .ok_or(MessagingError::Closed)?;
+
+        self.inner.factory.decode_reply(&reply)
+    }
+
+    async fn request_many<T, R>(
+        &self,
+        topic: &str,
+        payload: &T,
+        attrs: HashMap<String, String>,
+        options: RequestMany,
+    ) -> Result<Vec<Result<R, MessagingError>>, MessagingError>
+    where
+        T: serde::Serialize + Send + Sync,
+        R: serde::de::DeserializeOwned + Send,
+    {
+        let replies = self.send_to_inbox(topic, payload, attrs)?;
+        gather(&self.inner.factory, topic, replies, options).await
+    }
+
+    async fn request_stream<T, R>(
+        &self,
+        topic: &str,
+        payload: &T,
+        attrs: HashMap<String, String>,
+        timeout: Duration,
+    ) -> Result<ReplyStream<R>, MessagingError>
+    where
+        T: serde::Serialize + Send + Sync,
+        R: serde::de::DeserializeOwned + Send + 'static,
+    {
+        let replies = self.send_to_inbox(topic, payload, attrs)?;
+        Ok(reply_stream(
+            Arc::clone(&self.inner.factory),
+            topic.to_string(),
+            replies,
+            timeout,
+        ))
+    }
+
+    async fn close(&self) -> Result<(), MessagingError> {
+        Publisher::close(self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use bytes::Bytes;
+    use ro_config::config::nats::NatsConfig;
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::{Codec, Message, handler, reply_handler};
+
+    const WAIT: Duration = Duration::from_secs(1);
+
+    fn broker(base_path: &str) -> MemoryBroker {
+        let cfg = NatsConfig {
+            base_path: base_path.to_string(),
+            ..NatsConfig::default()
+        };
+        MemoryBroker::new("test", cfg, Vec::new())
+    }
+
+    /// Handler that forwards every message it receives, tagged with `tag`.
+    fn forward(tag: &'static str, tx: mpsc::UnboundedSender<(&'static str, Message)>) -> Handler {
+        handler(move |msg| {
+            let tx = tx.clone();
+            async move {
+                let _ = tx.send((tag, msg));
+                Ok(())
+            }
+        })
+    }
+
+    async fn recv<T>(rx: &mut mpsc::UnboundedReceiver<T>) -> T {
+        tokio::time::timeout(WAIT, rx.recv())
+            .await
+            .expect("message not delivered in time")
+            .expect("channel closed")
+    }
+
+    #[tokio::test]
+    async fn queue_group_delivers_round_robin() {
+        let broker = broker("");
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        for tag in ["a", "b"] {
+            broker
+                .queue_subscribe("jobs", "workers", forward(tag, tx.clone()))
+                .await
+                .unwrap();
+        }
+
+        for _ in 0..4 {
+            broker
+                .publish("jobs", Bytes::from_static(b"job"), HashMap::new())
+                .await
+                .unwrap();
+        }
+
+        let mut counts = HashMap::new();
+        for _ in 0..4 {
+            let (tag, _) = recv(&mut rx).await;
+            *counts.entry(tag).or_insert(0) += 1;
+        }
+        assert_eq!(counts, HashMap::from([("a", 2), ("b", 2)]));
+        assert!(rx.try_recv().is_err(), "each message goes to one member");
+    }
+
+    #[tokio::test]
+    async fn fan_out_reaches_every_subscriber() {
+        let broker = broker("");
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        broker
+            .subscribe("user.*", forward("wildcard", tx.clone()))
+            .await
+            .unwrap();
+        broker
+            .subscribe("user.created", forward("exact", tx.clone()))
+            .await
+            .unwrap();
+
+        broker
+            .publish("user.created", Bytes::from_static(b"{}"), HashMap::new())
+            .await
+            .unwrap();
+
+        let mut tags = vec![recv(&mut rx).await.0, recv(&mut rx).await.0];
+        tags.sort();
+        assert_eq!(tags, ["exact", "wildcard"]);
+    }
+
+    #[tokio::test]
+    async fn unsubscribe_id_keeps_other_subscriptions_to_the_topic() {
+        let broker = broker("");
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let first = broker
+            .subscribe("$SRV.PING", forward("first", tx.clone()))
+            .await
+            .unwrap();
+        broker
+            .subscribe("$SRV.PING", forward("second", tx.clone()))
+            .await
+            .unwrap();
+
+        broker.unsubscribe_id(first).await.unwrap();
+        broker
+            .publish("$SRV.PING", Bytes::from_static(b"{}"), HashMap::new())
+            .await
+            .unwrap();
+
+        assert_eq!(recv(&mut rx).await.0, "second");
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), rx.recv())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn request_gets_reply() {
+        let broker = broker("");
+        broker
+            .subscribe(
+                "math.double",
+                reply_handler(|msg: Message| async move {
+                    let n: i64 = msg.decode()?;
+                    Ok(Some(Encoding::Json.encode(&(n * 2))?))
+                }),
+            )
+            .await
+            .unwrap();
+
+        let reply: i64 = broker
+            .request("math.double", &21, HashMap::new(), WAIT)
+            .await
+            .unwrap();
+        assert_eq!(reply, 42);
+    }
+
+    #[tokio::test]
+    async fn request_without_responders_fails_fast() {
+        let broker = broker("");
+        let err = broker
+            .request::<_, i64>("math.double", &21, HashMap::new(), WAIT)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, MessagingError::NoResponders { ref subject } if subject == "math.double"),
+            "unexpected error: {err:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn base_path_prefixes_topics() {
+        let broker = broker("app");
+        assert_eq!(broker.factory().subject("user.created"), "app.user.created");
+        assert_eq!(broker.factory().subject("$SRV.PING"), "$SRV.PING");
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        broker
+            .subscribe("user.created", forward("sub", tx))
+            .await
+            .unwrap();
+        broker
+            .publish("user.created", Bytes::from_static(b"{}"), HashMap::new())
+            .await
+            .unwrap();
+
+        let (_, msg) = recv(&mut rx).await;
+        assert_eq!(msg.topic, "app.user.created");
+    }
+}
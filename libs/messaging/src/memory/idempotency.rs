@@ -0,0 +1,82 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+
+use crate::{
+    MessagingError,
+    error::IoOp,
+    idempotency::{Claim, IdempotencyStore},
+};
+
+/// Process-local `IdempotencyStore` with per-key expiry.
+///
+/// Only de-duplicates within one process; use a shared store (e.g. the
+/// Postgres inbox) when several consumers share a queue group.
+#[derive(Debug, Default)]
+pub struct MemoryIdempotencyStore {
+    seen: Mutex<HashMap<String, Seen>>,
+}
+
+#[derive(Debug)]
+struct Seen {
+    expires: Instant,
+    done: bool,
+}
+
+impl MemoryIdempotencyStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, HashMap<String, Seen>>, MessagingError> {
+        self.seen
+            .lock()
+            .map_err(|e| MessagingError::io(IoOp::Store, e.to_string()))
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for MemoryIdempotencyStore {
+    async fn try_claim(&self, key: &str, lease: Duration) -> Result<Claim, MessagingError> {
+        let mut seen = self.lock()?;
+        let now = Instant::now();
+        seen.retain(|_, s| s.expires > now);
+
+        match seen.get(key) {
+            Some(s) if s.done => Ok(Claim::Done),
+            Some(_) => Ok(Claim::InProgress),
+            None => {
+                seen.insert(
+                    key.to_string(),
+                    Seen {
+                        expires: now + lease,
+                        done: false,
+                    },
+                );
+                Ok(Claim::Acquired)
+            }
+        }
+    }
+
+    async fn complete(&self, key: &str, ttl: Duration) -> Result<(), MessagingError> {
+        self.lock()?.insert(
+            key.to_string(),
+            Seen {
+                expires: Instant::now() + ttl,
+                done: true,
+            },
+        );
+        Ok(())
+    }
+
+    async fn release(&self, key: &str) -> Result<(), MessagingError> {
+        if let Ok(mut seen) = self.seen.lock() {
+            seen.remove(key);
+        }
+        Ok(())
+    }
+}
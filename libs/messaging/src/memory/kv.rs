@@ -0,0 +1,249 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::Utc;
+use futures_util::{StreamExt, stream};
+use tokio::sync::broadcast;
+
+use crate::{
+    MessagingError,
+    error::IoOp,
+    kv::{KeyValueStore, KvEntry, KvOperation, KvWatch},
+    memory::subject::matches,
+};
+
+/// Watchers further behind than this miss changes.
+const WATCH_BUFFER: usize = 1024;
+
+/// Process-local `KeyValueStore`.
+///
+/// Only the latest revision of each key is kept. Per-key TTLs are always
+/// allowed and are enforced on read, so watchers see no event when a key
+/// expires. `update` keeps a key's TTL, restarting it from the update.
+#[derive(Debug, Clone)]
+pub struct MemoryKeyValue {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    state: Mutex<State>,
+    changes: broadcast::Sender<KvEntry>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    revision: u64,
+    entries: HashMap<String, Stored>,
+}
+
+#[derive(Debug)]
+struct Stored {
+    entry: KvEntry,
+    ttl: Option<Duration>,
+    expires: Option<Instant>,
+}
+
+impl Stored {
+    /// Holds a value (not deleted, not expired).
+    fn is_live(&self) -> bool {
+        self.entry.operation == KvOperation::Put && !self.is_expired()
+    }
+
+    fn is_expired(&self) -> bool {
+        self.expires.is_some_and(|e| e <= Instant::now())
+    }
+}
+
+impl MemoryKeyValue {
+    pub fn new() -> Self {
+        let (changes, _) = broadcast::channel(WATCH_BUFFER);
+        Self {
+            inner: Arc::new(Inner {
+                state: Mutex::new(State::default()),
+                changes,
+            }),
+        }
+    }
+
+    fn state(&self) -> Result<MutexGuard<'_, State>, MessagingError> {
+        self.inner
+            .state
+            .lock()
+            .map_err(|e| MessagingError::io(IoOp::Store, e.to_string()))
+    }
+
+    /// Record a new revision of `key` and notify watchers.
+    fn write(
+        &self,
+        state: &mut State,
+        key: &str,
+        value: Bytes,
+        operation: KvOperation,
+        ttl: Option<Duration>,
+    ) -> u64 {
+        state.revision += 1;
+        let entry = KvEntry {
+            key: key.to_string(),
+            value,
+            revision: state.revision,
+            created: Utc::now(),
+            operation,
+        };
+        let _ = self.inner.changes.send(entry.clone());
+        if operation == KvOperation::Purge {
+            state.entries.remove(key);
+        } else {
+            state.entries.insert(
+                key.to_string(),
+                Stored {
+                    entry,
+                    ttl,
+                    expires: ttl.map(|ttl| Instant::now() + ttl),
+                },
+            );
+        }
+        state.revision
+    }
+
+    fn create_maybe_ttl(
+        &self,
+        key: &str,
+        value: Bytes,
+        ttl: Option<Duration>,
+    ) -> Result<u64, MessagingError> {
+        let mut state = self.state()?;
+        if state.entries.get(key).is_some_and(Stored::is_live) {
+            return Err(conflict(key));
+        }
+        Ok(self.write(&mut state, key, value, KvOperation::Put, ttl))
+    }
+}
+
+impl Default for MemoryKeyValue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn conflict(key: &str) -> MessagingError {
+    MessagingError::Conflict {
+        key: key.to_string(),
+    }
+}
+
+#[async_trait]
+impl KeyValueStore for MemoryKeyValue {
+    async fn get(&self, key: &str) -> Result<Option<KvEntry>, MessagingError> {
+        let state = self.state()?;
+        Ok(state
+            .entries
+            .get(key)
+            .filter(|s| s.is_live())
+            .map(|s| s.entry.clone()))
+    }
+
+    async fn put(&self, key: &str, value: Bytes) -> Result<u64, MessagingError> {
+        let mut state = self.state()?;
+        Ok(self.write(&mut state, key, value, KvOperation::Put, None))
+    }
+
+    async fn create(&self, key: &str, value: Bytes) -> Result<u64, MessagingError> {
+        self.create_maybe_ttl(key, value, None)
+    }
+
+    async fn create_with_ttl(
+        &self,
+        key: &str,
+        value: Bytes,
+        ttl: Duration,
+    ) -> Result<u64, MessagingError> {
+        self.create_maybe_ttl(key, value, Some(ttl))
+    }
+
+    async fn update(&self, key: &str, value: Bytes, revision: u64) -> Result<u64, MessagingError> {
+        let mut state = self.state()?;
+        // An expired key is absent, as on a server that already removed it.
+        let stored = state.entries.get(key).filter(|s| !s.is_expired());
+        let current = stored.map_or(0, |s| s.entry.revision);
+        if current != revision {
+            return Err(conflict(key));
+        }
+        let ttl = stored.and_then(|s| s.ttl);
+        Ok(self.write(&mut state, key, value, KvOperation::Put, ttl))
+    }
+
+    async fn delete(&self, key: &str, revision: Option<u64>) -> Result<(), MessagingError> {
+        let mut state = self.state()?;
+        if let Some(revision) = revision {
+            // As in `update`, an expired key is absent.
+            let current = state
+                .entries
+                .get(key)
+                .filter(|s| !s.is_expired())
+                .map_or(0, |s| s.entry.revision);
+            if current != revision {
+                return Err(conflict(key));
+            }
+        }
+        self.write(&mut state, key, Bytes::new(), KvOperation::Delete, None);
+        Ok(())
+    }
+
+    async fn purge(&self, key: &str) -> Result<(), MessagingError> {
+        let mut state = self.state()?;
+        self.write(&mut state, key, Bytes::new(), KvOperation::Purge, None);
+        Ok(())
+    }
+
+    async fn keys(&self) -> Result<Vec<String>, MessagingError> {
+        let state = self.state()?;
+        Ok(state
+            .entries
+            .iter()
+            .filter(|(_, s)| s.is_live())
+            .map(|(key, _)| key.clone())
+            .collect())
+    }
+
+    async fn watch(&self, pattern: &str) -> Result<KvWatch, MessagingError> {
+        // Subscribe under the lock so no write falls between the snapshot
+        // and the live changes.
+        let (current, changes) = {
+            let state = self.state()?;
+            let mut current: Vec<_> = state
+                .entries
+                .values()
+                .filter(|s| s.is_live() && matches(pattern, &s.entry.key))
+                .map(|s| Ok(s.entry.clone()))
+                .collect();
+            current.sort_by_key(|e| e.as_ref().map_or(0, |e| e.revision));
+            (current, self.inner.changes.subscribe())
+        };
+
+        let pattern = pattern.to_string();
+        let live = stream::unfold(changes, move |mut changes| {
+            let pattern = pattern.clone();
+            async move {
+                loop {
+                    match changes.recv().await {
+                        Ok(entry) if matches(&pattern, &entry.key) => {
+                            return Some((Ok(entry), changes));
+                        }
+                        Ok(_) => continue,
+                        Err(broadcast::error::RecvError::Lagged(missed)) => {
+                            tracing::warn!(missed, "KV: watcher lagged, changes dropped");
+                        }
+                        Err(broadcast::error::RecvError::Closed) => return None,
+                    }
+                }
+            }
+        });
+        Ok(stream::iter(current).chain(live).boxed())
+    }
+}
@@ -0,0 +1,12 @@
+pub mod broker;
+pub mod idempotency;
+pub mod kv;
+pub mod object_store;
+pub mod scheduler;
+pub mod subject;
+
+pub use broker::MemoryBroker;
+pub use idempotency::MemoryIdempotencyStore;
+pub use kv::MemoryKeyValue;
+pub use object_store::MemoryObjectStore;
+pub use scheduler::MemoryScheduler;
@@ -0,0 +1,112 @@
+use std::{
+    collections::HashMap,
+    io::Cursor,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use chrono::Utc;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::{
+    MessagingError,
+    error::IoOp,
+    object_store::{ObjectInfo, ObjectMeta, ObjectReader, ObjectStore},
+};
+
+/// Default upload chunk size, as in JetStream.
+const DEFAULT_CHUNK_SIZE: usize = 128 * 1024;
+
+type Objects = HashMap<String, (ObjectInfo, Bytes)>;
+
+/// Process-local `ObjectStore`. Content is held in memory and no digest is
+/// computed.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryObjectStore {
+    objects: Arc<Mutex<Objects>>,
+}
+
+impl MemoryObjectStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn objects(&self) -> Result<MutexGuard<'_, Objects>, MessagingError> {
+        self.objects
+            .lock()
+            .map_err(|e| MessagingError::io(IoOp::Store, e.to_string()))
+    }
+}
+
+#[async_trait]
+impl ObjectStore for MemoryObjectStore {
+    async fn put(
+        &self,
+        meta: ObjectMeta,
+        data: &mut (dyn AsyncRead + Send + Unpin),
+    ) -> Result<ObjectInfo, MessagingError> {
+        let chunk_size = meta.chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE).max(1);
+        let mut content = BytesMut::new();
+        let mut chunk = vec![0; chunk_size];
+        let mut chunks = 0;
+        loop {
+            let mut filled = 0;
+            while filled < chunk_size {
+                let n = data
+                    .read(&mut chunk[filled..])
+                    .await
+                    .map_err(|e| MessagingError::io(IoOp::Store, e))?;
+                if n == 0 {
+                    break;
+                }
+                filled += n;
+            }
+            if filled == 0 {
+                break;
+            }
+            content.extend_from_slice(&chunk[..filled]);
+            chunks += 1;
+            if filled < chunk_size {
+                break;
+            }
+        }
+
+        let info = ObjectInfo {
+            name: meta.name,
+            description: meta.description,
+            metadata: meta.metadata,
+            size: content.len(),
+            chunks,
+            digest: None,
+            modified: Some(Utc::now()),
+        };
+        self.objects()?
+            .insert(info.name.clone(), (info.clone(), content.freeze()));
+        Ok(info)
+    }
+
+    async fn get(&self, name: &str) -> Result<Option<(ObjectInfo, ObjectReader)>, MessagingError> {
+        Ok(self.objects()?.get(name).map(|(info, content)| {
+            let reader: ObjectReader = Box::new(Cursor::new(content.clone()));
+            (info.clone(), reader)
+        }))
+    }
+
+    async fn info(&self, name: &str) -> Result<Option<ObjectInfo>, MessagingError> {
+        Ok(self.objects()?.get(name).map(|(info, _)| info.clone()))
+    }
+
+    async fn delete(&self, name: &str) -> Result<(), MessagingError> {
+        self.objects()?.remove(name);
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<Vec<ObjectInfo>, MessagingError> {
+        Ok(self
+            .objects()?
+            .values()
+            .map(|(info, _)| info.clone())
+            .collect())
+    }
+}
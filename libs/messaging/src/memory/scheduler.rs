@@ -0,0 +1,118 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use tokio::task::JoinHandle;
+
+use ro_common::id::generate_nanoid;
+
+use crate::{
+    Encoding, MessagingError, Publisher, error::IoOp, nats::factory::HEADER_MESSAGE_ID,
+    scheduler::ScheduledPublisher,
+};
+
+type Pending = HashMap<String, JoinHandle<()>>;
+
+/// `ScheduledPublisher` over any `Publisher`, with one timer task per
+/// scheduled message.
+///
+/// Nothing is persisted: scheduled messages are lost when the process
+/// exits. Use it in tests and local development, or for delays where that
+/// is acceptable.
+#[derive(Debug, Clone)]
+pub struct MemoryScheduler {
+    publisher: Arc<dyn Publisher>,
+    pending: Arc<Mutex<Pending>>,
+}
+
+impl MemoryScheduler {
+    pub fn new(publisher: Arc<dyn Publisher>) -> Self {
+        Self {
+            publisher,
+            pending: Arc::default(),
+        }
+    }
+
+    /// Number of messages waiting to be published.
+    pub fn pending(&self) -> usize {
+        self.pending.lock().map_or(0, |p| p.len())
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, Pending>, MessagingError> {
+        self.pending
+            .lock()
+            .map_err(|e| MessagingError::io(IoOp::Store, e.to_string()))
+    }
+}
+
+#[async_trait]
+impl Publisher for MemoryScheduler {
+    async fn publish(
+        &self,
+        topic: &str,
+        data: Bytes,
+        attrs: HashMap<String, String>,
+    ) -> Result<(), MessagingError> {
+        self.publisher.publish(topic, data, attrs).await
+    }
+
+    /// Drop every pending message, then close the inner publisher.
+    async fn close(&self) -> Result<(), MessagingError> {
+        for (_, timer) in self.lock()?.drain() {
+            timer.abort();
+        }
+        self.publisher.close().await
+    }
+
+    fn encoding(&self) -> Encoding {
+        self.publisher.encoding()
+    }
+}
+
+#[async_trait]
+impl ScheduledPublisher for MemoryScheduler {
+    async fn publish_at(
+        &self,
+        topic: &str,
+        data: Bytes,
+        mut attrs: HashMap<String, String>,
+        at: DateTime<Utc>,
+    ) -> Result<String, MessagingError> {
+        let id = attrs
+            .entry(HEADER_MESSAGE_ID.to_string())
+            .or_insert_with(generate_nanoid)
+            .clone();
+        let delay = (at - Utc::now()).to_std().unwrap_or_default();
+
+        // Hold the lock while spawning so the timer cannot fire and remove
+        // its entry before it is inserted.
+        let mut pending = self.lock()?;
+        let timer = tokio::spawn({
+            let publisher = Arc::clone(&self.publisher);
+            let pending = Arc::clone(&self.pending);
+            let topic = topic.to_string();
+            let id = id.clone();
+            async move {
+                tokio::time::sleep(delay).await;
+                if let Ok(mut pending) = pending.lock() {
+                    pending.remove(&id);
+                }
+                if let Err(e) = publisher.publish(&topic, data, attrs).await {
+                    tracing::warn!(%id, %topic, error = %e, "Scheduler: publish failed");
+                }
+            }
+        });
+        if let Some(previous) = pending.insert(id.clone(), timer) {
+            previous.abort();
+        }
+        Ok(id)
+    }
+
+    async fn cancel(&self, id: &str) -> Result<bool, MessagingError> {
+        Ok(self.lock()?.remove(id).map(|timer| timer.abort()).is_some())
+    }
+}
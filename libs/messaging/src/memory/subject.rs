@@ -0,0 +1,50 @@
+/// NATS-style subject matching.
+///
+/// `*` matches exactly one token, `>` matches one or more trailing tokens.
+/// `matches("user.*", "user.created")` → `true`,
+/// `matches("user.>", "user.profile.updated")` → `true`,
+/// `matches("user.>", "user")` → `false`.
+pub fn matches(pattern: &str, subject: &str) -> bool {
+    let mut pattern = pattern.split('.');
+    let mut subject = subject.split('.');
+    loop {
+        match (pattern.next(), subject.next()) {
+            (Some(">"), Some(_)) => return true,
+            (Some("*"), Some(_)) => continue,
+            (Some(p), Some(s)) if p == s => continue,
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::matches;
+
+    #[test]
+    fn literal_subjects_match_exactly() {
+        assert!(matches("user.created", "user.created"));
+        assert!(!matches("user.created", "user.deleted"));
+        assert!(!matches("user.created", "user.created.1"));
+        assert!(!matches("user.created.1", "user.created"));
+    }
+
+    #[test]
+    fn star_matches_exactly_one_token() {
+        assert!(matches("user.*", "user.created"));
+        assert!(matches("*.created", "user.created"));
+        assert!(matches("user.*.1", "user.created.1"));
+        assert!(!matches("user.*", "user"));
+        assert!(!matches("user.*", "user.profile.updated"));
+    }
+
+    #[test]
+    fn full_wildcard_matches_one_or_more_trailing_tokens() {
+        assert!(matches("user.>", "user.created"));
+        assert!(matches("user.>", "user.profile.updated"));
+        assert!(matches(">", "user"));
+        assert!(!matches("user.>", "user"));
+        assert!(!matches("user.>", "order.created"));
+    }
+}
@@ -1,9 +1,19 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Duration};
 
 use bytes::Bytes;
-use serde::de::DeserializeOwned;
+use ro_common::context::{RequestContext, SYSTEM_ACTOR};
+use serde::{Serialize, de::DeserializeOwned};
 
-use crate::MessagingError;
+use crate::{
+    MessagingError,
+    ack::{AckHandle, AckKind},
+    codec::{Codec, Encoding, HEADER_CONTENT_TYPE},
+    nats::factory::{
+        HEADER_MESSAGE_ID, HEADER_PARTITION_KEY, HEADER_REQUEST_ID, HEADER_TENANT_ID,
+        HEADER_USER_ID,
+    },
+    request::Replier,
+};
 
 /// A transport-agnostic message envelope.
 ///
@@ -17,6 +27,10 @@ pub struct Message {
     pub data: Bytes,
     /// Key/value metadata (NATS headers, trace IDs, user_id, etc.)
     pub attrs: HashMap<String, String>,
+    /// Acknowledgement handle for the delivery (no-op on core NATS)
+    pub(crate) ack: AckHandle,
+    /// Reply channel when the message is a request
+    pub(crate) replier: Option<Replier>,
 }
 
 impl Message {
@@ -25,6 +39,8 @@ impl Message {
             topic: topic.into(),
             data: data.into(),
             attrs: HashMap::new(),
+            ack: AckHandle::default(),
+            replier: None,
         }
     }
 
@@ -33,21 +49,107 @@ impl Message {
         self
     }
 
+    /// Order this message with others of the same `key`; see
+    /// `crate::partition`.
+    pub fn with_partition_key(self, key: impl Into<String>) -> Self {
+        self.with_attr(HEADER_PARTITION_KEY, key)
+    }
+
     pub fn attr(&self, key: &str) -> Option<&str> {
         self.attrs.get(key).map(|s| s.as_str())
     }
 
+    /// Request context the message was published under.
+    ///
+    /// Subscriptions run handlers inside this context, so publishes and
+    /// `DbContext::current()` in a handler keep the original actor.
+    pub fn context(&self) -> RequestContext {
+        RequestContext {
+            actor_id: self
+                .attr(HEADER_USER_ID)
+                .filter(|actor| *actor != SYSTEM_ACTOR)
+                .map(str::to_string),
+            request_id: self.attr(HEADER_REQUEST_ID).map(str::to_string),
+            tenant_id: self.attr(HEADER_TENANT_ID).map(str::to_string),
+        }
+    }
+
+    /// Stamp `ctx` as attrs, keeping any already set.
+    ///
+    /// Use for messages published later, e.g. via the outbox, when the
+    /// context is no longer in scope.
+    pub fn with_context(mut self, ctx: &RequestContext) -> Self {
+        let fields = [
+            (HEADER_USER_ID, ctx.actor_id.as_ref()),
+            (HEADER_REQUEST_ID, ctx.request_id.as_ref()),
+            (HEADER_TENANT_ID, ctx.tenant_id.as_ref()),
+        ];
+        for (key, value) in fields {
+            if let Some(value) = value {
+                self.attrs
+                    .entry(key.to_string())
+                    .or_insert_with(|| value.clone());
+            }
+        }
+        self
+    }
+
+    /// Message id (`Nats-Msg-Id`), stamped on publish.
+    pub fn id(&self) -> Option<&str> {
+        self.attr(HEADER_MESSAGE_ID)
+    }
+
+    /// Acknowledge successful processing.
+    ///
+    /// Handlers rarely need this: the subscription acks on `Ok` and naks on
+    /// `Err` unless the message was already settled.
+    pub async fn ack(&self) -> Result<(), MessagingError> {
+        self.ack.send(AckKind::Ack).await
+    }
+
+    /// Ask for redelivery, optionally after `delay`.
+    pub async fn nak(&self, delay: Option<Duration>) -> Result<(), MessagingError> {
+        self.ack.send(AckKind::Nak(delay)).await
+    }
+
+    /// Stop redelivery of a message that can never be processed.
+    pub async fn term(&self) -> Result<(), MessagingError> {
+        self.ack.send(AckKind::Term).await
+    }
+
+    /// Extend the ack deadline while long-running work continues.
+    pub async fn in_progress(&self) -> Result<(), MessagingError> {
+        self.ack.send(AckKind::InProgress).await
+    }
+
+    /// Payload encoding from the `content-type` attr (JSON when absent).
+    pub fn encoding(&self) -> Result<Encoding, MessagingError> {
+        Encoding::from_attrs(&self.attrs)
+    }
+
+    /// Decode the payload with the codec named by its `content-type`.
+    pub fn decode<T: DeserializeOwned>(&self) -> Result<T, MessagingError> {
+        self.encoding()?.decode(&self.data)
+    }
+
     pub fn json<T: DeserializeOwned>(&self) -> Result<T, MessagingError> {
-        serde_json::from_slice(&self.data)
-            .map_err(|e| MessagingError::Deserialization(e.to_string()))
+        Encoding::Json.decode(&self.data)
+    }
+
+    /// Encode `payload` with `encoding` and stamp its `content-type`.
+    pub fn encode<T: Serialize>(
+        topic: impl Into<String>,
+        payload: &T,
+        encoding: Encoding,
+    ) -> Result<Self, MessagingError> {
+        let data = encoding.encode(payload)?;
+        Ok(Self::new(topic, data).with_attr(HEADER_CONTENT_TYPE, encoding.content_type()))
     }
 
-    pub fn from_json<T: serde::Serialize>(
+    pub fn from_json<T: Serialize>(
         topic: impl Into<String>,
         payload: &T,
     ) -> Result<Self, MessagingError> {
-        let data = serde_json::to_vec(payload)
-            .map_err(|e| MessagingError::Serialization(e.to_string()))?;
-        Ok(Self::new(topic, Bytes::from(data)))
+        Self::encode(topic, payload, Encoding::Json)
     }
 }
@@ -0,0 +1,38 @@
+use async_nats::jetstream::{self, message::Acker};
+use async_trait::async_trait;
+
+use crate::{
+    MessagingError,
+    ack::{AckHandle, AckKind, Acknowledger},
+    error::IoOp,
+    subscription::Delivery,
+};
+
+/// Maps `AckKind` onto JetStream ack replies.
+pub struct JetStreamAcker(pub Acker);
+
+#[async_trait]
+impl Acknowledger for JetStreamAcker {
+    async fn send(&self, kind: AckKind) -> Result<(), MessagingError> {
+        let kind = match kind {
+            AckKind::Ack => jetstream::AckKind::Ack,
+            AckKind::Nak(delay) => jetstream::AckKind::Nak(delay),
+            AckKind::Term => jetstream::AckKind::Term,
+            AckKind::InProgress => jetstream::AckKind::Progress,
+        };
+        self.0
+            .ack_with(kind)
+            .await
+            .map_err(|e| MessagingError::io(IoOp::Ack, e))
+    }
+}
+
+impl From<jetstream::Message> for Delivery {
+    fn from(msg: jetstream::Message) -> Self {
+        let (msg, acker) = msg.split();
+        Self {
+            msg,
+            ack: AckHandle::new(JetStreamAcker(acker)),
+        }
+    }
+}
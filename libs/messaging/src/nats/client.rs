@@ -1,6 +1,6 @@
 use std::{collections::HashMap, sync::Arc, time::Duration};
 
-use async_nats::ConnectOptions;
+use async_nats::{ConnectOptions, jetstream};
 use async_trait::async_trait;
 use bytes::Bytes;
 use futures_util::StreamExt;
@@ -9,20 +9,33 @@ use tokio::sync::Mutex;
 use ro_config::config::nats::NatsConfig;
 
 use crate::{
-    Broker, Handler, MessagingError, Publisher, QueueSubscriber, Subscriber,
+    Broker, Encoding, Handler, MessagingError, Publisher, QueueSubscriber, SubscribeOptions,
+    Subscriber, SubscriptionId,
+    error::IoOp,
     nats::{
         factory::MessageFactory,
         middleware::{MiddlewareFn, NatsHandlerFn, apply_middleware},
+        status::{ConnectionState, ConnectionStatus, StatusTracker},
     },
+    request::{ReplyStream, RequestMany, gather, reply_stream},
+    subscription::{Delivery, Subscription, spawn_drain, wrap_handler},
 };
 
 #[derive(Debug, Clone)]
 pub struct NatsClient {
     inner: async_nats::Client,
-    factory: Arc<MessageFactory>,
-    middlewares: Arc<Vec<MiddlewareFn>>,
-    /// topic → abort handle for the subscription drain task
-    subscriptions: Arc<Mutex<HashMap<String, tokio::task::AbortHandle>>>,
+    pub(crate) jetstream: jetstream::Context,
+    pub(crate) cfg: Arc<NatsConfig>,
+    pub(crate) factory: Arc<MessageFactory>,
+    pub(crate) middlewares: Arc<Vec<MiddlewareFn>>,
+    /// Flow control for subscriptions made through this handle
+    pub(crate) subscribe_options: SubscribeOptions,
+    /// Publish through JetStream and wait for the stream's PubAck
+    persistent: bool,
+    /// Connection state, fed by client events
+    status: Arc<StatusTracker>,
+    /// topic → running subscription (drain loop + handlers)
+    pub(crate) subscriptions: Arc<Mutex<HashMap<SubscriptionId, (String, Subscription)>>>,
 }
 
 impl NatsClient {
@@ -34,8 +47,17 @@ impl NatsClient {
         if !cfg.enabled {
             return Err(MessagingError::Closed);
         }
+        if let (Some(timeout), Some(js)) = (cfg.handler_timeout(), &cfg.jetstream)
+            && timeout >= js.ack_wait()
+        {
+            tracing::warn!(
+                handler_timeout_secs = cfg.handler_timeout_secs,
+                ack_wait_secs = js.ack_wait_secs,
+                "NATS: handler timeout is not below ack_wait, slow messages get redelivered while still running"
+            );
+        }
 
-        let url = cfg.url.clone();
+        let urls = cfg.server_urls();
         let ping = cfg.ping_interval();
         let allow_reconnect = cfg.allow_reconnect;
         let max_reconnects = cfg.max_reconnects;
@@ -44,7 +66,14 @@ impl NatsClient {
         let cfg = Arc::new(cfg);
         let factory = Arc::new(MessageFactory::new(name.clone(), Arc::clone(&cfg)));
 
-        let mut opts = ConnectOptions::new().name(&name).ping_interval(ping);
+        let mut opts = ConnectOptions::new()
+            .name(&name)
+            .ping_interval(ping)
+            .connection_timeout(cfg.connection_timeout());
+        if let Some(prefix) = &cfg.inbox_prefix {
+            opts = opts.custom_inbox_prefix(prefix);
+        }
+        opts = apply_security(opts, &cfg).await?;
 
         if !allow_reconnect {
             opts = opts.max_reconnects(Some(0));
@@ -53,92 +82,211 @@ impl NatsClient {
         }
         // max_reconnects < 0 → infinite (default in async-nats)
 
+        let status = Arc::new(StatusTracker::new(name.clone()));
+        let events = Arc::clone(&status);
         opts = opts
             .reconnect_delay_callback(|attempts| {
-                tracing::info!("NATS: reconnected");
-                std::time::Duration::from_millis(std::cmp::min((attempts * 100) as u64, 8000))
+                let delay =
+                    std::time::Duration::from_millis(std::cmp::min((attempts * 100) as u64, 8000));
+                tracing::debug!(
+                    attempts,
+                    delay_ms = delay.as_millis() as u64,
+                    "NATS: reconnecting"
+                );
+                delay
             })
-            .event_callback(|event| async move {
-                match event {
-                    async_nats::Event::Disconnected => println!("disconnected"),
-                    async_nats::Event::Connected => println!("reconnected"),
-                    async_nats::Event::ClientError(err) => {
-                        println!("client error occurred: {}", err)
-                    }
-                    other => println!("other event happened: {}", other),
-                }
+            .event_callback(move |event| {
+                let events = Arc::clone(&events);
+                async move { events.on_event(event) }
             });
 
-        let inner = opts
-            .connect(url)
-            .await
-            .map_err(|e| MessagingError::Subscribe(e.to_string()))?;
+        let inner = opts.connect(urls).await?;
 
-        tracing::info!(name, "NATS: connected");
+        // The connection may already have dropped (and reported it).
+        status.transition(ConnectionState::Connecting, ConnectionState::Connected);
 
         Ok(Self {
+            status,
+            subscribe_options: SubscribeOptions::from_config(&cfg),
+            persistent: false,
+            jetstream: jetstream::new(inner.clone()),
             inner,
+            cfg,
             factory,
             middlewares: Arc::new(middlewares),
             subscriptions: Arc::new(Mutex::new(HashMap::new())),
         })
     }
 
+    /// A handle on the same connection with `extra` middlewares appended
+    /// (innermost) for subscriptions made through it.
+    ///
+    /// Lets a middleware capture the client itself, e.g. as the
+    /// dead-letter publisher of `retry_middleware`.
+    pub fn with_middlewares(&self, extra: Vec<MiddlewareFn>) -> Self {
+        let mut middlewares = self.middlewares.as_ref().clone();
+        middlewares.extend(extra);
+        Self {
+            middlewares: Arc::new(middlewares),
+            ..self.clone()
+        }
+    }
+
+    /// A handle on the same connection whose subscriptions use `options`
+    /// (concurrency limit, ordered mode) instead of the config defaults.
+    ///
+    /// ```rust,ignore
+    /// nats.with_subscribe_options(SubscribeOptions::default().ordered())
+    ///     .durable_subscribe("user.created", "worker", handler)
+    ///     .await?;
+    /// ```
+    pub fn with_subscribe_options(&self, options: SubscribeOptions) -> Self {
+        Self {
+            subscribe_options: options,
+            ..self.clone()
+        }
+    }
+
+    /// A handle on the same connection whose `publish` goes through
+    /// JetStream and returns only once the stream acknowledged the message.
+    ///
+    /// Use it for topics captured by the configured stream when the caller
+    /// must know the message is stored (outbox relay, dead letters). The
+    /// `Nats-Msg-Id` header still deduplicates retried publishes.
+    pub fn persistent(&self) -> Self {
+        Self {
+            persistent: true,
+            ..self.clone()
+        }
+    }
+
+    /// Current connection state, reconnect count and last error.
+    ///
+    /// Cheap to call; use it from readiness / health probes.
+    pub fn status(&self) -> ConnectionStatus {
+        self.status.snapshot()
+    }
+
+    /// Message factory (subjects, headers, CloudEvents) for this client.
+    pub fn factory(&self) -> &MessageFactory {
+        &self.factory
+    }
+
     /// Access the raw `async_nats::Client` for advanced use cases.
     pub fn inner(&self) -> &async_nats::Client {
         &self.inner
     }
 
-    /// Wrap a domain `Handler` into a transport-level `NatsHandlerFn`
-    /// (factory reads the message and converts it, then calls the domain handler).
-    fn wrap_handler(&self, handler: Handler) -> NatsHandlerFn {
-        let factory = Arc::clone(&self.factory);
-        Arc::new(move |nats_msg: async_nats::Message| {
-            let handler = Arc::clone(&handler);
-            let factory = Arc::clone(&factory);
+    /// Wrap a domain `Handler`; replies are published on this connection.
+    pub(crate) fn wrap_handler(&self, handler: Handler) -> NatsHandlerFn {
+        let inner = self.inner.clone();
+        let publish_reply: NatsHandlerFn = Arc::new(move |reply: async_nats::Message| {
+            let inner = inner.clone();
             Box::pin(async move {
-                let msg = factory.read_message(nats_msg)?;
-                handler(msg)
+                inner
+                    .publish_with_headers(
+                        reply.subject,
+                        reply.headers.unwrap_or_default(),
+                        reply.payload,
+                    )
                     .await
-                    .map(|_| ())
-                    .map_err(|e| MessagingError::Handler(e.to_string()))
+                    .map_err(MessagingError::from)
             })
-        })
+        });
+        wrap_handler(Arc::clone(&self.factory), handler, publish_reply)
     }
 
-    /// Spawn a drain loop for a subscription stream; return the `AbortHandle`.
+    /// Gracefully stop every subscription, then drain the connection.
     ///
-    /// One Tokio task per subscription (same as Go goroutine per Subscribe).
-    fn spawn_drain<S>(
+    /// Subscriptions stop receiving at once; handlers already running get
+    /// up to `deadline` to finish (and settle their messages) before they
+    /// are aborted. Pending publishes are flushed and the connection is
+    /// closed afterwards. `close` does the same with
+    /// `NatsConfig::drain_timeout`.
+    pub async fn drain(&self, deadline: Duration) -> Result<(), MessagingError> {
+        let subscriptions: Vec<_> = self.subscriptions.lock().await.drain().collect();
+        let count = subscriptions.len();
+
+        let finished = futures_util::future::join_all(
+            subscriptions
+                .into_iter()
+                .map(|(_, (topic, sub))| async move { sub.drain(&topic, deadline).await }),
+        )
+        .await;
+        let aborted = finished.iter().filter(|done| !**done).count();
+        tracing::info!(
+            subscriptions = count,
+            aborted,
+            "NATS: subscriptions drained"
+        );
+
+        self.inner
+            .drain()
+            .await
+            .map_err(|_| MessagingError::Closed)?;
+        self.status.set_state(ConnectionState::Closed);
+        Ok(())
+    }
+
+    /// Publish a request whose replies go to a fresh inbox subscription,
+    /// for multi-reply requests. Returns the request subject and the inbox.
+    async fn send_to_inbox<T: serde::Serialize>(
         &self,
-        topic: String,
-        mut stream: S,
-        handler: NatsHandlerFn,
-    ) -> tokio::task::AbortHandle
-    where
-        S: futures_util::Stream<Item = async_nats::Message> + Send + Unpin + 'static,
-    {
-        tokio::spawn(async move {
-            tracing::debug!(topic, "NATS: subscription started");
-            while let Some(msg) = stream.next().await {
-                let h = Arc::clone(&handler);
-                let t = topic.clone();
-                tokio::spawn(async move {
-                    if let Err(e) = h(msg).await {
-                        tracing::error!(topic = %t, error = %e, "NATS: handler error");
-                    }
-                });
-            }
-            tracing::debug!(topic, "NATS: subscription ended");
-        })
-        .abort_handle()
+        topic: &str,
+        payload: &T,
+        mut attrs: HashMap<String, String>,
+    ) -> Result<(String, async_nats::Subscriber), MessagingError> {
+        let data = self.factory.encode(payload, &mut attrs)?;
+        let msg = self.factory.build_msg(topic, None, data, attrs)?;
+
+        let inbox = self.inner.new_inbox();
+        let replies = self
+            .inner
+            .subscribe(inbox.clone())
+            .await
+            .map_err(MessagingError::from)?;
+        self.inner
+            .publish_with_reply_and_headers(
+                msg.subject.clone(),
+                inbox,
+                msg.headers.unwrap_or_default(),
+                msg.payload,
+            )
+            .await
+            .map_err(MessagingError::from)?;
+        Ok((msg.subject.to_string(), replies))
     }
 
-    async fn cancel_subscription(&self, topic: &str) {
-        let mut map = self.subscriptions.lock().await;
-        if let Some(handle) = map.remove(topic) {
-            handle.abort();
-        }
+    pub(crate) async fn add_subscription(
+        &self,
+        subject: String,
+        sub: Subscription,
+    ) -> SubscriptionId {
+        let id = SubscriptionId::next();
+        self.subscriptions.lock().await.insert(id, (subject, sub));
+        id
+    }
+
+    /// Remove the subscriptions `pred` selects and drain them.
+    async fn cancel_subscriptions(&self, pred: impl Fn(SubscriptionId, &str) -> bool) {
+        let removed: Vec<_> = {
+            let mut subscriptions = self.subscriptions.lock().await;
+            let ids: Vec<_> = subscriptions
+                .iter()
+                .filter(|(id, (subject, _))| pred(**id, subject))
+                .map(|(id, _)| *id)
+                .collect();
+            ids.into_iter()
+                .filter_map(|id| subscriptions.remove(&id))
+                .collect()
+        };
+        let deadline = self.cfg.drain_timeout();
+        futures_util::future::join_all(
+            removed
+                .into_iter()
+                .map(|(subject, sub)| async move { sub.drain(&subject, deadline).await }),
+        )
+        .await;
     }
 }
 
@@ -154,27 +302,37 @@ impl Publisher for NatsClient {
         let nats_msg = self.factory.build_msg(topic, None, data, attrs)?;
 
         let inner = self.inner.clone();
+        let jetstream = self.persistent.then(|| self.jetstream.clone());
         let subject = nats_msg.subject.clone();
         let payload = nats_msg.payload.clone();
         let headers = nats_msg.headers.clone();
 
         let pub_fn: NatsHandlerFn = Arc::new(move |_msg| {
             let inner = inner.clone();
+            let jetstream = jetstream.clone();
             let subject = subject.clone();
             let payload = payload.clone();
             let headers = headers.clone();
 
             Box::pin(async move {
-                if let Some(hdrs) = headers {
+                if let Some(js) = jetstream {
+                    let ack = js
+                        .publish_with_headers(subject, headers.unwrap_or_default(), payload)
+                        .await
+                        .map_err(|e| MessagingError::io(IoOp::Publish, e))?;
+                    ack.await
+                        .map(|_| ())
+                        .map_err(|e| MessagingError::io(IoOp::Publish, e))
+                } else if let Some(hdrs) = headers {
                     inner
                         .publish_with_headers(subject, hdrs, payload)
                         .await
-                        .map_err(|e| MessagingError::Publish(e.to_string()))
+                        .map_err(MessagingError::from)
                 } else {
                     inner
                         .publish(subject, payload)
                         .await
-                        .map_err(|e| MessagingError::Publish(e.to_string()))
+                        .map_err(MessagingError::from)
                 }
             })
         });
@@ -185,41 +343,52 @@ impl Publisher for NatsClient {
         chained(nats_msg).await
     }
 
-    /// Drain the connection, aborting all active subscription tasks.
+    /// Gracefully stop all subscriptions and drain the connection
+    /// (see `NatsClient::drain`).
     async fn close(&self) -> Result<(), MessagingError> {
-        let mut map = self.subscriptions.lock().await;
-        for handle in map.values() {
-            handle.abort();
-        }
-        map.clear();
-        drop(map);
+        self.drain(self.cfg.drain_timeout()).await
+    }
 
-        self.inner.drain().await.map_err(|_| MessagingError::Closed)
+    fn encoding(&self) -> Encoding {
+        self.factory.encoding()
     }
 }
 
 #[async_trait]
 impl Subscriber for NatsClient {
-    async fn subscribe(&self, topic: &str, handler: Handler) -> Result<(), MessagingError> {
+    async fn subscribe(
+        &self,
+        topic: &str,
+        handler: Handler,
+    ) -> Result<SubscriptionId, MessagingError> {
         let subject = self.factory.subject(topic);
 
         let stream = self
             .inner
             .subscribe(subject.clone())
             .await
-            .map_err(|e| MessagingError::Subscribe(e.to_string()))?;
+            .map_err(MessagingError::from)?;
 
         let transport_handler =
             apply_middleware("subscriber", self.wrap_handler(handler), &self.middlewares);
 
-        let handle = self.spawn_drain(subject.clone(), stream, transport_handler);
-        self.subscriptions.lock().await.insert(subject, handle);
-        Ok(())
+        let handle = spawn_drain(
+            subject.clone(),
+            stream.map(Delivery::from),
+            transport_handler,
+            self.subscribe_options,
+        );
+        Ok(self.add_subscription(subject, handle).await)
     }
 
     async fn unsubscribe(&self, topic: &str) -> Result<(), MessagingError> {
         let subject = self.factory.subject(topic);
-        self.cancel_subscription(&subject).await;
+        self.cancel_subscriptions(|_, s| s == subject).await;
+        Ok(())
+    }
+
+    async fn unsubscribe_id(&self, id: SubscriptionId) -> Result<(), MessagingError> {
+        self.cancel_subscriptions(|i, _| i == id).await;
         Ok(())
     }
 
@@ -236,14 +405,14 @@ impl QueueSubscriber for NatsClient {
         topic: &str,
         group: &str,
         handler: Handler,
-    ) -> Result<(), MessagingError> {
+    ) -> Result<SubscriptionId, MessagingError> {
         let subject = self.factory.subject(topic);
 
         let stream = self
             .inner
             .queue_subscribe(subject.clone(), group.to_string())
             .await
-            .map_err(|e| MessagingError::Subscribe(e.to_string()))?;
+            .map_err(MessagingError::from)?;
 
         let transport_handler = apply_middleware(
             "queue_subscribe",
@@ -251,9 +420,13 @@ impl QueueSubscriber for NatsClient {
             &self.middlewares,
         );
 
-        let handle = self.spawn_drain(subject.clone(), stream, transport_handler);
-        self.subscriptions.lock().await.insert(subject, handle);
-        Ok(())
+        let handle = spawn_drain(
+            subject.clone(),
+            stream.map(Delivery::from),
+            transport_handler,
+            self.subscribe_options,
+        );
+        Ok(self.add_subscription(subject, handle).await)
     }
 
     async fn unsubscribe(&self, topic: &str) -> Result<(), MessagingError> {
@@ -271,36 +444,135 @@ impl Broker for NatsClient {
         &self,
         topic: &str,
         payload: &T,
-        attrs: HashMap<String, String>,
+        mut attrs: HashMap<String, String>,
         timeout: Duration,
     ) -> Result<R, MessagingError>
     where
         T: serde::Serialize + Send + Sync,
         R: serde::de::DeserializeOwned,
     {
-        let data = serde_json::to_vec(payload)
-            .map_err(|e| MessagingError::Serialization(e.to_string()))?;
+        let data = self.factory.encode(payload, &mut attrs)?;
 
-        let msg = self
-            .factory
-            .build_msg(topic, None, Bytes::from(data), attrs)?;
+        let msg = self.factory.build_msg(topic, None, data, attrs)?;
+
+        let mut request = async_nats::Request::new().payload(msg.payload);
+        if let Some(headers) = msg.headers {
+            request = request.headers(headers);
+        }
 
         let subject = msg.subject.clone();
+        let reply = tokio::time::timeout(timeout, self.inner.send_request(msg.subject, request))
+            .await
+            .map_err(|_| MessagingError::Timeout(timeout))?
+            .map_err(|e| MessagingError::from_request(e, &subject, timeout))?;
 
-        let reply = tokio::time::timeout(
-            timeout,
-            self.inner
-                .send_request(subject, async_nats::Request::new().payload(msg.payload)),
-        )
-        .await
-        .map_err(|_| MessagingError::Request("request timed out".into()))?
-        .map_err(|e| MessagingError::Request(e.to_string()))?;
+        self.factory.decode_reply(&reply)
+    }
 
-        serde_json::from_slice(&reply.payload)
-            .map_err(|e| MessagingError::Deserialization(e.to_string()))
+    async fn request_many<T, R>(
+        &self,
+        topic: &str,
+        payload: &T,
+        attrs: HashMap<String, String>,
+        options: RequestMany,
+    ) -> Result<Vec<Result<R, MessagingError>>, MessagingError>
+    where
+        T: serde::Serialize + Send + Sync,
+        R: serde::de::DeserializeOwned + Send,
+    {
+        let (subject, replies) = self.send_to_inbox(topic, payload, attrs).await?;
+        gather(&self.factory, &subject, replies, options).await
+    }
+
+    async fn request_stream<T, R>(
+        &self,
+        topic: &str,
+        payload: &T,
+        attrs: HashMap<String, String>,
+        timeout: Duration,
+    ) -> Result<ReplyStream<R>, MessagingError>
+    where
+        T: serde::Serialize + Send + Sync,
+        R: serde::de::DeserializeOwned + Send + 'static,
+    {
+        let (subject, replies) = self.send_to_inbox(topic, payload, attrs).await?;
+        Ok(reply_stream(
+            Arc::clone(&self.factory),
+            subject,
+            replies,
+            timeout,
+        ))
     }
 
     async fn close(&self) -> Result<(), MessagingError> {
         Publisher::close(self).await
     }
 }
+
+/// Apply `cfg.auth` and `cfg.tls` to `opts`.
+async fn apply_security(
+    mut opts: ConnectOptions,
+    cfg: &NatsConfig,
+) -> Result<ConnectOptions, MessagingError> {
+    let secret_err = |e: std::io::Error| MessagingError::io(IoOp::Connect, e);
+
+    if let Some(auth) = &cfg.auth {
+        if let Some(path) = &auth.creds_file {
+            opts = opts.credentials_file(path).await.map_err(secret_err)?;
+        } else if let Some(seed) = read_secret(&auth.nkey_seed, &auth.nkey_seed_file)
+            .await
+            .map_err(secret_err)?
+        {
+            opts = opts.nkey(seed);
+        } else if let Some(token) = read_secret(&auth.token, &auth.token_file)
+            .await
+            .map_err(secret_err)?
+        {
+            opts = opts.token(token);
+        } else if let Some(user) = &auth.user {
+            let password = read_secret(&auth.password, &auth.password_file)
+                .await
+                .map_err(secret_err)?
+                .unwrap_or_default();
+            opts = opts.user_and_password(user.clone(), password);
+        }
+    }
+
+    if let Some(tls) = &cfg.tls {
+        opts = opts.require_tls(tls.required);
+        if tls.handshake_first {
+            opts = opts.tls_first();
+        }
+        if let Some(ca) = &tls.ca_file {
+            opts = opts.add_root_certificates(ca.into());
+        }
+        match (&tls.cert_file, &tls.key_file) {
+            (Some(cert), Some(key)) => {
+                opts = opts.add_client_certificate(cert.into(), key.into());
+            }
+            (None, None) => {}
+            _ => {
+                return Err(MessagingError::io(
+                    IoOp::Connect,
+                    "tls cert_file and key_file must be set together",
+                ));
+            }
+        }
+    }
+
+    Ok(opts)
+}
+
+/// Inline `value`, else the trimmed contents of `file`.
+async fn read_secret(
+    value: &Option<String>,
+    file: &Option<String>,
+) -> std::io::Result<Option<String>> {
+    match (value, file) {
+        (Some(value), _) => Ok(Some(value.clone())),
+        (None, Some(path)) => tokio::fs::read_to_string(path)
+            .await
+            .map(|s| Some(s.trim().to_string())),
+        (None, None) => Ok(None),
+    }
+}
@@ -4,44 +4,147 @@ use async_nats::header::{HeaderMap, HeaderName, HeaderValue};
 use bytes::Bytes;
 use chrono::{SecondsFormat, Utc};
 use opentelemetry::global;
-use opentelemetry::propagation::Extractor;
+use ro_common::{context::RequestContext, id::generate_nanoid};
 use ro_config::config::nats::NatsConfig;
+use serde::{Serialize, de::DeserializeOwned};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 use crate::Message;
 use crate::MessagingError;
+use crate::ack::AckHandle;
+use crate::cloudevent::CloudEvent;
+use crate::codec::{Codec, Encoding, HEADER_CONTENT_TYPE};
+use crate::error::CodecDirection;
 use crate::nats::headers::NatsHeaderExtractor;
 use crate::nats::headers::NatsHeaderInjector;
+use crate::nats::headers::headers_to_attrs;
+use crate::partition;
 
 pub const HEADER_USER_ID: &str = "user_id";
 pub const HEADER_FROM: &str = "from";
 pub const HEADER_START_TIME: &str = "start_time";
+/// Request context (see `ro_common::context::RequestContext`).
+pub const HEADER_REQUEST_ID: &str = "request_id";
+pub const HEADER_TENANT_ID: &str = "tenant_id";
+/// Unique message id; also used by JetStream for publish de-duplication.
+pub const HEADER_MESSAGE_ID: &str = "Nats-Msg-Id";
+/// Ordering key; see `crate::partition`.
+pub const HEADER_PARTITION_KEY: &str = "partition-key";
+/// Marks the last reply of a streamed response (see `crate::request`).
+pub const HEADER_STREAM_END: &str = "stream_end";
+/// Error replies (NATS micro service convention).
+pub const HEADER_SERVICE_ERROR: &str = "Nats-Service-Error";
+pub const HEADER_SERVICE_ERROR_CODE: &str = "Nats-Service-Error-Code";
+/// Longest error description put in a header; the body keeps the full text.
+pub(crate) const MAX_ERROR_HEADER_LEN: usize = 256;
+/// Dead-letter metadata (see `retry_middleware`).
+pub const HEADER_DLQ_SUBJECT: &str = "dlq_subject";
+pub const HEADER_DLQ_REASON: &str = "dlq_reason";
+pub const HEADER_DLQ_ATTEMPTS: &str = "dlq_attempts";
 
 #[derive(Debug)]
 pub struct MessageFactory {
     name: String,
     cfg: Arc<NatsConfig>,
+    encoding: Encoding,
 }
 
 impl MessageFactory {
     pub fn new(name: String, config: Arc<NatsConfig>) -> Self {
-        Self { name, cfg: config }
+        let encoding = config.codec.parse().unwrap_or_else(|e| {
+            tracing::warn!(codec = %config.codec, error = %e, "NATS: unknown codec, using JSON");
+            Encoding::Json
+        });
+        Self {
+            name,
+            cfg: config,
+            encoding,
+        }
+    }
+
+    /// Default payload codec (`NatsConfig::codec`).
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Encode a request payload with the default codec and stamp its
+    /// `content-type` in `attrs` (unless the caller set one).
+    pub fn encode<T: Serialize>(
+        &self,
+        payload: &T,
+        attrs: &mut HashMap<String, String>,
+    ) -> Result<Bytes, MessagingError> {
+        let encoding = match attrs.get(HEADER_CONTENT_TYPE) {
+            Some(ct) => Encoding::from_content_type(ct)
+                .ok_or_else(|| MessagingError::encode(format!("unsupported content-type {ct}")))?,
+            None => self.encoding,
+        };
+        attrs.insert(
+            HEADER_CONTENT_TYPE.to_string(),
+            encoding.content_type().to_string(),
+        );
+        encoding.encode(payload)
+    }
+
+    /// Decode a reply, surfacing error replies as `MessagingError::Service`.
+    ///
+    /// The codec comes from the reply's `content-type`, falling back to JSON.
+    pub fn decode_reply<R: DeserializeOwned>(
+        &self,
+        reply: &async_nats::Message,
+    ) -> Result<R, MessagingError> {
+        if let Some(err) = self.reply_error(reply) {
+            return Err(err);
+        }
+        let attrs = reply
+            .headers
+            .as_ref()
+            .map(headers_to_attrs)
+            .unwrap_or_default();
+        Encoding::from_attrs(&attrs)?.decode(&reply.payload)
     }
 
+    /// Apply `base_path` to `pattern`.
+    ///
+    /// System subjects (`$SRV.*`, `$JS.*`, ...) are never prefixed.
     pub fn subject(&self, pattern: &str) -> String {
-        if self.cfg.base_path.is_empty() {
+        if self.cfg.base_path.is_empty() || pattern.starts_with('$') {
             pattern.to_string()
         } else {
             format!("{}.{}", self.cfg.base_path, pattern)
         }
     }
 
+    /// Start a CloudEvent produced by this service.
+    ///
+    /// `id` is a fresh nanoid, `source` the client name, `time` now, and
+    /// `payload` is encoded with the default codec (`datacontenttype`).
+    /// Add `subject` or extensions with the `CloudEvent::with_*` methods.
+    pub fn cloud_event<T: Serialize>(
+        &self,
+        ty: &str,
+        payload: &T,
+    ) -> Result<CloudEvent, MessagingError> {
+        let data = self.encoding.encode(payload)?;
+        Ok(
+            CloudEvent::new(generate_nanoid(), self.name.clone(), ty, data)
+                .with_time(Utc::now())
+                .with_datacontenttype(self.encoding.content_type()),
+        )
+    }
+
     /// Build a `nats::Message` ready for sending.
     ///
+    /// With `NatsConfig::partitioning` set, a message carrying a
+    /// `partition-key` attr goes to `<pattern>.<partition>`.
+    ///
     /// Headers written:
-    ///   - `user_id`    — from `actor_id` (replaces Go's `GetUserIDFromCtx`)
+    ///   - `user_id`    — `actor_id`, else the current `RequestContext` actor,
+    ///     else `system` (replaces Go's `GetUserIDFromCtx`)
+    ///   - `request_id` / `tenant_id` — from the current `RequestContext`
     ///   - `from`       — config.name
     ///   - `start_time` — RFC3339 nanoseconds
+    ///   - `Nats-Msg-Id` — a fresh nanoid, unless `attrs` already carries one
     ///   - `traceparent`/ `tracestate` — injected from `tracing::Span::current()`
     ///   - any extra `attrs` provided by the caller
     pub fn build_msg(
@@ -51,7 +154,13 @@ impl MessageFactory {
         data: Bytes,
         attrs: HashMap<String, String>,
     ) -> Result<async_nats::Message, MessagingError> {
-        let subject = self.subject(pattern);
+        let subject = match (&self.cfg.partitioning, attrs.get(HEADER_PARTITION_KEY)) {
+            (Some(partitioning), Some(key)) => self.subject(&partition::subject(
+                pattern,
+                partition::partition_for(key, partitioning.partitions),
+            )),
+            _ => self.subject(pattern),
+        };
         let headers = self.build_headers(actor_id, attrs)?;
 
         Ok(async_nats::Message {
@@ -65,10 +174,82 @@ impl MessageFactory {
         })
     }
 
+    /// Build the reply to a request that arrived with reply subject `reply`.
+    ///
+    /// The reply subject is used verbatim (no `base_path`). Headers are the
+    /// same as `build_msg`; an `Ok` reply is labelled with `content_type`
+    /// (the request's, since replies answer in the caller's encoding). An
+    /// `Err` reply carries `Nats-Service-Error` / `Nats-Service-Error-Code`
+    /// headers and a `{"code", "description"}` JSON body. The header holds
+    /// the first line of the description, truncated; the body the full text.
+    pub fn build_reply(
+        &self,
+        reply: async_nats::Subject,
+        result: Result<Bytes, &MessagingError>,
+        content_type: Option<&str>,
+    ) -> Result<async_nats::Message, MessagingError> {
+        let mut headers = self.build_headers(None, HashMap::new())?;
+
+        let payload = match result {
+            Ok(data) => {
+                if let Some(ct) = content_type {
+                    self.insert_header(&mut headers, HEADER_CONTENT_TYPE, ct)?;
+                }
+                data
+            }
+            Err(err) => {
+                let (code, description) = match err {
+                    MessagingError::Service { code, description } => (*code, description.clone()),
+                    MessagingError::Codec {
+                        direction: CodecDirection::Decode,
+                        ..
+                    } => (400, err.to_string()),
+                    MessagingError::Timeout(_) => (504, err.to_string()),
+                    _ => (500, err.to_string()),
+                };
+                self.insert_header(
+                    &mut headers,
+                    HEADER_SERVICE_ERROR,
+                    &header_text(&description, MAX_ERROR_HEADER_LEN),
+                )?;
+                self.insert_header(&mut headers, HEADER_SERVICE_ERROR_CODE, &code.to_string())?;
+                self.insert_header(
+                    &mut headers,
+                    HEADER_CONTENT_TYPE,
+                    Encoding::Json.content_type(),
+                )?;
+                let body = serde_json::json!({ "code": code, "description": description });
+                Bytes::from(body.to_string())
+            }
+        };
+
+        Ok(async_nats::Message {
+            subject: reply,
+            reply: None,
+            payload,
+            headers: Some(headers),
+            status: None,
+            description: None,
+            length: 0,
+        })
+    }
+
+    /// Return the error carried by an error reply, if `reply` is one.
+    pub fn reply_error(&self, reply: &async_nats::Message) -> Option<MessagingError> {
+        let headers = reply.headers.as_ref()?;
+        let description = headers.get(HEADER_SERVICE_ERROR)?.as_str().to_string();
+        let code = headers
+            .get(HEADER_SERVICE_ERROR_CODE)
+            .and_then(|v| v.as_str().parse().ok())
+            .unwrap_or(500);
+        Some(MessagingError::Service { code, description })
+    }
+
     /// Convert an incoming `async_nats::Message` into a `pubsub::Message`.
     ///
     /// All NATS headers become `attrs` (including traceparent so the caller
     /// can extract the parent span context for their own child span).
+    /// The ack handle of the delivery being processed is attached.
     ///
     pub fn read_message(&self, msg: async_nats::Message) -> Result<Message, MessagingError> {
         let topic = msg.subject.to_string();
@@ -78,24 +259,23 @@ impl MessageFactory {
         let attrs: HashMap<String, String> = msg
             .headers
             .as_ref()
-            .map(|h| {
-                let extractor = NatsHeaderExtractor(h);
-                extractor
-                    .keys()
-                    .into_iter()
-                    .filter_map(|k| extractor.get(k).map(|v| (k.to_string(), v.to_string())))
-                    .collect()
-            })
+            .map(headers_to_attrs)
             .unwrap_or_default();
 
-        Ok(Message { topic, data, attrs })
+        Ok(Message {
+            topic,
+            data,
+            attrs,
+            ack: AckHandle::current(),
+            replier: None,
+        })
     }
 
     /// Extract the parent OTel context from an inbound message's headers.
     ///
     /// Use this inside a subscriber handler to link the child span to
     /// the upstream trace:
-    /// ```rust  
+    /// ```rust,ignore
     /// let parent_cx = factory.extract_trace_context(&msg);  
     /// let span = tracer.start_with_context("nats.consume", &parent_cx);  
     /// ```
@@ -115,14 +295,28 @@ impl MessageFactory {
         attrs: HashMap<String, String>,
     ) -> Result<HeaderMap, MessagingError> {
         let mut headers = HeaderMap::new();
+        let ctx = RequestContext::current();
 
-        self.insert_header(&mut headers, HEADER_USER_ID, actor_id.unwrap_or("system"))?;
+        self.insert_header(
+            &mut headers,
+            HEADER_USER_ID,
+            actor_id.unwrap_or(ctx.actor_or_system()),
+        )?;
+        if let Some(request_id) = &ctx.request_id {
+            self.insert_header(&mut headers, HEADER_REQUEST_ID, request_id)?;
+        }
+        if let Some(tenant_id) = &ctx.tenant_id {
+            self.insert_header(&mut headers, HEADER_TENANT_ID, tenant_id)?;
+        }
         self.insert_header(&mut headers, HEADER_FROM, &self.name.clone())?;
         self.insert_header(
             &mut headers,
             HEADER_START_TIME,
             &Utc::now().to_rfc3339_opts(SecondsFormat::Nanos, true),
         )?;
+        if !attrs.contains_key(HEADER_MESSAGE_ID) {
+            self.insert_header(&mut headers, HEADER_MESSAGE_ID, &generate_nanoid())?;
+        }
 
         let otel_cx = tracing::Span::current().context();
         global::get_text_map_propagator(|propagator| {
@@ -143,9 +337,61 @@ impl MessageFactory {
         value: &str,
     ) -> Result<(), MessagingError> {
         let name = HeaderName::from_str(key)
-            .map_err(|e| MessagingError::Publish(format!("invalid header name {key}: {e}")))?;
+            .map_err(|e| MessagingError::encode(format!("invalid header name {key}: {e}")))?;
         let val = HeaderValue::from(value);
         map.insert(name, val);
         Ok(())
     }
 }
+
+/// `text` made safe for a header value: its first line, without control
+/// characters, cut to at most `max` bytes on a char boundary.
+pub(crate) fn header_text(text: &str, max: usize) -> String {
+    let line = text.lines().next().unwrap_or_default().trim();
+    let mut out = String::with_capacity(line.len().min(max));
+    for c in line.chars().filter(|c| !c.is_control()) {
+        if out.len() + c.len_utf8() > max {
+            break;
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn factory() -> MessageFactory {
+        MessageFactory::new("test".to_string(), Arc::new(NatsConfig::default()))
+    }
+
+    #[test]
+    fn header_text_keeps_first_line_within_limit() {
+        assert_eq!(header_text("boom\r\nat line 2", 64), "boom");
+        assert_eq!(header_text("tab\there", 64), "tabhere");
+        assert_eq!(header_text("ééé", 5), "éé");
+        assert_eq!(header_text("", 8), "");
+    }
+
+    #[test]
+    fn error_reply_header_is_single_line_and_body_is_full() {
+        let description = format!("first line\nsecond line {}", "x".repeat(500));
+        let err = MessagingError::service(500, description.clone());
+        let reply = factory()
+            .build_reply("_INBOX.1".into(), Err(&err), None)
+            .unwrap();
+
+        let headers = reply.headers.unwrap();
+        assert_eq!(
+            headers.get(HEADER_SERVICE_ERROR).unwrap().as_str(),
+            "first line"
+        );
+        assert_eq!(
+            headers.get(HEADER_SERVICE_ERROR_CODE).unwrap().as_str(),
+            "500"
+        );
+        let body: serde_json::Value = serde_json::from_slice(&reply.payload).unwrap();
+        assert_eq!(body["description"], description.as_str());
+    }
+}
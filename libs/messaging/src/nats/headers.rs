@@ -1,4 +1,4 @@
-use std::str::FromStr;
+use std::{collections::HashMap, str::FromStr};
 
 use async_nats::header::{HeaderMap, HeaderName, HeaderValue};
 use opentelemetry::propagation::{Extractor, Injector};
@@ -17,10 +17,20 @@ pub struct NatsHeaderExtractor<'a>(pub &'a HeaderMap);
 
 impl Extractor for NatsHeaderExtractor<'_> {
     fn get(&self, key: &str) -> Option<&str> {
-        self.0.get(key).and_then(|v| Some(v.as_str()))
+        self.0.get(key).map(|v| v.as_str())
     }
 
     fn keys(&self) -> Vec<&str> {
         self.0.iter().map(|(k, _)| k.as_ref()).collect()
     }
 }
+
+/// Copy every header into a plain `attrs` map.
+pub fn headers_to_attrs(headers: &HeaderMap) -> HashMap<String, String> {
+    let extractor = NatsHeaderExtractor(headers);
+    extractor
+        .keys()
+        .into_iter()
+        .filter_map(|k| extractor.get(k).map(|v| (k.to_string(), v.to_string())))
+        .collect()
+}
@@ -0,0 +1,189 @@
+use async_nats::jetstream::{
+    consumer::{AckPolicy, pull},
+    stream::{self, StorageType},
+};
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use ro_config::config::nats::JetStreamConfig;
+
+use crate::{
+    DurableSubscriber, Handler, MessagingError, Publisher, Subscriber, SubscriptionId,
+    error::IoOp,
+    nats::middleware::apply_middleware,
+    subscription::{Delivery, spawn_drain},
+};
+
+use super::NatsClient;
+
+impl NatsClient {
+    /// Bind to the stream described by `NatsConfig::jetstream`, creating it
+    /// on first use.
+    ///
+    /// Stream subjects get the same `base_path` prefix as published topics,
+    /// so core-NATS publishes to those subjects are captured by the stream.
+    /// An existing stream whose subjects differ from the config is updated.
+    pub async fn ensure_stream(&self) -> Result<stream::Stream, MessagingError> {
+        let js_cfg = self.jetstream_config()?;
+
+        let storage = match js_cfg.storage.as_str() {
+            "memory" => StorageType::Memory,
+            _ => StorageType::File,
+        };
+
+        let subjects: Vec<String> = js_cfg
+            .subjects
+            .iter()
+            .map(|s| self.factory.subject(s))
+            .collect();
+
+        let stream = self
+            .jetstream
+            .get_or_create_stream(stream::Config {
+                name: js_cfg.stream.clone(),
+                subjects: subjects.clone(),
+                storage,
+                num_replicas: js_cfg.replicas,
+                max_age: js_cfg.max_age(),
+                ..Default::default()
+            })
+            .await
+            .map_err(|e| MessagingError::io(IoOp::Subscribe, e))?;
+
+        // Subjects added to the config (e.g. `dlq.>`) must reach a stream
+        // created by an earlier release.
+        let current = &stream.cached_info().config;
+        if current.subjects == subjects {
+            return Ok(stream);
+        }
+        tracing::info!(
+            stream = js_cfg.stream,
+            ?subjects,
+            "NATS: updating stream subjects"
+        );
+        self.jetstream
+            .update_stream(stream::Config {
+                subjects,
+                ..current.clone()
+            })
+            .await
+            .map_err(|e| MessagingError::io(IoOp::Subscribe, e))?;
+        self.jetstream
+            .get_stream(&js_cfg.stream)
+            .await
+            .map_err(|e| MessagingError::io(IoOp::Subscribe, e))
+    }
+
+    /// Access the JetStream context for advanced use cases.
+    pub fn jetstream(&self) -> &async_nats::jetstream::Context {
+        &self.jetstream
+    }
+
+    fn jetstream_config(&self) -> Result<&JetStreamConfig, MessagingError> {
+        self.cfg
+            .jetstream
+            .as_ref()
+            .ok_or_else(|| MessagingError::io(IoOp::Subscribe, "jetstream is not configured"))
+    }
+}
+
+#[async_trait]
+impl DurableSubscriber for NatsClient {
+    /// Bind a durable pull consumer filtered on `topic` and drain it.
+    ///
+    /// Handlers may settle explicitly through `Message::ack`/`nak`/`term`/
+    /// `in_progress`; otherwise the message is acked when the handler
+    /// succeeds and nak'd (redelivered by the server) when it fails.
+    ///
+    /// An ordered handle (`SubscribeOptions::ordered`) creates the consumer
+    /// with one unacknowledged message at a time, so redeliveries keep the
+    /// order too. An existing consumer whose filter, ack wait, max deliver
+    /// or max ack pending differ is updated to match.
+    async fn durable_subscribe(
+        &self,
+        topic: &str,
+        durable: &str,
+        handler: Handler,
+    ) -> Result<SubscriptionId, MessagingError> {
+        let js_cfg = self.jetstream_config()?;
+        let stream = self.ensure_stream().await?;
+
+        let subject = self.factory.subject(topic);
+
+        let config = pull::Config {
+            durable_name: Some(durable.to_string()),
+            filter_subject: subject.clone(),
+            ack_policy: AckPolicy::Explicit,
+            ack_wait: js_cfg.ack_wait(),
+            max_deliver: js_cfg.max_deliver,
+            max_ack_pending: if self.subscribe_options.ordered {
+                1
+            } else {
+                js_cfg.max_ack_pending
+            },
+            ..Default::default()
+        };
+
+        let mut consumer = stream
+            .get_or_create_consumer(durable, config.clone())
+            .await
+            .map_err(|e| MessagingError::io(IoOp::Subscribe, e))?;
+
+        // An existing durable keeps the settings it was created with;
+        // bring it in line with the current config and ordering.
+        let current = &consumer.cached_info().config;
+        if current.filter_subject != config.filter_subject
+            || current.ack_wait != config.ack_wait
+            || current.max_deliver != config.max_deliver
+            || current.max_ack_pending != config.max_ack_pending
+        {
+            tracing::info!(durable, subject, "NATS: updating durable consumer config");
+            consumer = stream
+                .update_consumer(config)
+                .await
+                .map_err(|e| MessagingError::io(IoOp::Subscribe, e))?;
+        }
+
+        let messages = consumer
+            .messages()
+            .await
+            .map_err(|e| MessagingError::io(IoOp::Subscribe, e))?;
+
+        let topic_label = subject.clone();
+        let deliveries = messages
+            .filter_map(move |res| {
+                let topic = topic_label.clone();
+                async move {
+                    match res {
+                        Ok(msg) => Some(Delivery::from(msg)),
+                        Err(e) => {
+                            tracing::warn!(topic, error = %e, "NATS: jetstream pull error");
+                            None
+                        }
+                    }
+                }
+            })
+            .boxed();
+
+        let transport_handler = apply_middleware(
+            "durable_subscribe",
+            self.wrap_handler(handler),
+            &self.middlewares,
+        );
+
+        let handle = spawn_drain(
+            subject.clone(),
+            deliveries,
+            transport_handler,
+            self.subscribe_options,
+        );
+        Ok(self.add_subscription(subject, handle).await)
+    }
+
+    async fn unsubscribe(&self, topic: &str) -> Result<(), MessagingError> {
+        Subscriber::unsubscribe(self, topic).await
+    }
+
+    async fn close(&self) -> Result<(), MessagingError> {
+        Publisher::close(self).await
+    }
+}
@@ -0,0 +1,189 @@
+use std::time::Duration;
+
+use async_nats::jetstream::{
+    ErrorCode,
+    context::{GetStreamError, GetStreamErrorKind},
+    kv::{self, CreateErrorKind, Operation, UpdateErrorKind},
+    stream::StorageType,
+};
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures_util::{StreamExt, TryStreamExt};
+
+use crate::{
+    MessagingError,
+    error::IoOp,
+    kv::{BucketOptions, KeyValueStore, KvEntry, KvOperation, KvWatch},
+};
+
+use super::NatsClient;
+
+/// A JetStream key-value bucket.
+#[derive(Debug, Clone)]
+pub struct NatsKeyValue {
+    store: kv::Store,
+}
+
+impl NatsClient {
+    /// Open the key-value bucket `options.bucket`, creating it with
+    /// `options` if it does not exist yet.
+    pub async fn key_value(&self, options: &BucketOptions) -> Result<NatsKeyValue, MessagingError> {
+        let store = match self.jetstream.get_key_value(&options.bucket).await {
+            Ok(store) => store,
+            Err(e) if !is_bucket_not_found(&e) => return Err(store_err(e)),
+            Err(_) => self
+                .jetstream
+                .create_key_value(kv::Config {
+                    bucket: options.bucket.clone(),
+                    history: options.history,
+                    max_age: options.max_age.unwrap_or_default(),
+                    storage: storage_type(options),
+                    num_replicas: options.replicas,
+                    // Markers are what lets keys carry their own TTL.
+                    limit_markers: options.per_key_ttl.then(|| Duration::from_secs(1)),
+                    ..Default::default()
+                })
+                .await
+                .map_err(store_err)?,
+        };
+        Ok(NatsKeyValue { store })
+    }
+}
+
+pub(crate) fn storage_type(options: &BucketOptions) -> StorageType {
+    if options.in_memory {
+        StorageType::Memory
+    } else {
+        StorageType::File
+    }
+}
+
+/// Whether opening a bucket failed because its stream does not exist,
+/// as opposed to e.g. a timeout or missing permissions.
+pub(crate) fn is_bucket_not_found(err: &dyn std::error::Error) -> bool {
+    err.source()
+        .and_then(|source| source.downcast_ref::<GetStreamError>())
+        .is_some_and(|source| {
+            matches!(
+                source.kind(),
+                GetStreamErrorKind::JetStream(e) if e.error_code() == ErrorCode::STREAM_NOT_FOUND
+            )
+        })
+}
+
+pub(crate) fn store_err(err: impl std::error::Error + Send + Sync + 'static) -> MessagingError {
+    MessagingError::io(IoOp::Store, err)
+}
+
+fn conflict(key: &str) -> MessagingError {
+    MessagingError::Conflict {
+        key: key.to_string(),
+    }
+}
+
+fn to_entry(entry: kv::Entry) -> KvEntry {
+    KvEntry {
+        key: entry.key,
+        value: entry.value,
+        revision: entry.revision,
+        created: DateTime::<Utc>::from_timestamp(
+            entry.created.unix_timestamp(),
+            entry.created.nanosecond(),
+        )
+        .unwrap_or_default(),
+        operation: match entry.operation {
+            Operation::Put => KvOperation::Put,
+            Operation::Delete => KvOperation::Delete,
+            Operation::Purge => KvOperation::Purge,
+        },
+    }
+}
+
+#[async_trait]
+impl KeyValueStore for NatsKeyValue {
+    async fn get(&self, key: &str) -> Result<Option<KvEntry>, MessagingError> {
+        let entry = self.store.entry(key).await.map_err(store_err)?;
+        Ok(entry
+            .filter(|e| e.operation == Operation::Put)
+            .map(to_entry))
+    }
+
+    async fn put(&self, key: &str, value: Bytes) -> Result<u64, MessagingError> {
+        self.store.put(key, value).await.map_err(store_err)
+    }
+
+    async fn create(&self, key: &str, value: Bytes) -> Result<u64, MessagingError> {
+        self.store
+            .create(key, value)
+            .await
+            .map_err(|e| match e.kind() {
+                CreateErrorKind::AlreadyExists => conflict(key),
+                _ => store_err(e),
+            })
+    }
+
+    async fn create_with_ttl(
+        &self,
+        key: &str,
+        value: Bytes,
+        ttl: Duration,
+    ) -> Result<u64, MessagingError> {
+        self.store
+            .create_with_ttl(key, value, ttl)
+            .await
+            .map_err(|e| match e.kind() {
+                CreateErrorKind::AlreadyExists => conflict(key),
+                _ => store_err(e),
+            })
+    }
+
+    async fn update(&self, key: &str, value: Bytes, revision: u64) -> Result<u64, MessagingError> {
+        self.store
+            .update(key, value, revision)
+            .await
+            .map_err(|e| match e.kind() {
+                UpdateErrorKind::WrongLastRevision => conflict(key),
+                _ => store_err(e),
+            })
+    }
+
+    async fn delete(&self, key: &str, revision: Option<u64>) -> Result<(), MessagingError> {
+        match self.store.delete_expect_revision(key, revision).await {
+            Ok(()) => Ok(()),
+            // The client does not tell a revision mismatch apart from other
+            // failures, so look at what is there now.
+            Err(e) => match (revision, self.store.entry(key).await) {
+                (Some(expected), Ok(Some(entry))) if entry.revision != expected => {
+                    Err(conflict(key))
+                }
+                _ => Err(store_err(e)),
+            },
+        }
+    }
+
+    async fn purge(&self, key: &str) -> Result<(), MessagingError> {
+        self.store.purge(key).await.map_err(store_err)
+    }
+
+    async fn keys(&self) -> Result<Vec<String>, MessagingError> {
+        self.store
+            .keys()
+            .await
+            .map_err(store_err)?
+            .map_err(store_err)
+            .try_collect()
+            .await
+    }
+
+    async fn watch(&self, pattern: &str) -> Result<KvWatch, MessagingError> {
+        let watch = self
+            .store
+            .watch_with_history(pattern)
+            .await
+            .map_err(store_err)?;
+        Ok(watch
+            .map(|entry| entry.map(to_entry).map_err(store_err))
+            .boxed())
+    }
+}
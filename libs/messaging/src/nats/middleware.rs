@@ -1,7 +1,20 @@
-use std::{future::Future, pin::Pin, sync::Arc};
+use std::{future::Future, pin::Pin, sync::Arc, time::Duration};
 
-use crate::MessagingError;
-use crate::nats::headers::NatsHeaderExtractor;
+use opentelemetry::KeyValue;
+use ro_config::config::nats::RetryConfig;
+use ro_telemetry::meter::messaging::{
+    CONSUMED_COUNT, DEAD_LETTER_COUNT, DELIVERY_LAG, DUPLICATE_COUNT, ERROR_COUNT, HANDLER_LATENCY,
+    PAYLOAD_SIZE, PUBLISHED_COUNT, RETRY_COUNT,
+};
+
+use crate::ack::{AckHandle, AckKind};
+use crate::nats::factory::{
+    HEADER_DLQ_ATTEMPTS, HEADER_DLQ_REASON, HEADER_DLQ_SUBJECT, HEADER_MESSAGE_ID,
+    HEADER_PARTITION_KEY, HEADER_START_TIME, MAX_ERROR_HEADER_LEN, header_text,
+};
+use crate::nats::headers::{NatsHeaderExtractor, headers_to_attrs};
+use crate::subscription::with_timeout;
+use crate::{Claim, IdempotencyStore, MessagingError, Publisher};
 
 /// A single NATS message handler at the transport level.
 /// Distinct from `pubsub::Handler` (which is domain-level, returns `Option<Bytes>`).
@@ -27,7 +40,7 @@ pub struct MiddlewareFn {
 impl MiddlewareFn {
     /// Create a named middleware.
     ///
-    /// ```rust  
+    /// ```rust,ignore
     /// let mw = MiddlewareFn::new("tracing", |op, next| { ... });  
     /// ```
     pub fn new<F>(name: &'static str, f: F) -> Self
@@ -105,8 +118,491 @@ pub fn tracing_middleware() -> MiddlewareFn {
                 );
                 let _ = span.set_parent(parent_cx);
 
-                inner(msg).instrument(span).await
+                let unfinished = Unfinished(Some(span.clone()));
+                let result = inner(msg).instrument(span).await;
+                unfinished.finish();
+                result
+            }) as Pin<Box<dyn Future<Output = Result<(), MessagingError>> + Send>>
+        })
+    })
+}
+
+/// Marks the message span if the chain is dropped before it completes,
+/// e.g. by the subscription's `handler_timeout`, which runs outside the
+/// middleware chain, or by a drain deadline.
+struct Unfinished(Option<tracing::Span>);
+
+impl Unfinished {
+    fn finish(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for Unfinished {
+    fn drop(&mut self) {
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+        if let Some(span) = self.0.take() {
+            span.set_attribute("messaging.cancelled", true);
+            span.set_status(opentelemetry::trace::Status::error(
+                "handler cancelled (timed out or aborted)",
+            ));
+        }
+    }
+}
+
+/// Built-in: timeout middleware.
+///
+/// Cancels inbound handlers that run longer than `timeout` and fails them
+/// with `MessagingError::Timeout`, which the retry middleware treats like
+/// any other handler error. Publishes are not affected.
+///
+/// Place it after `tracing_middleware` (so the timeout is recorded on the
+/// message span) and after `retry_middleware` (so each attempt gets the
+/// full budget). For a per-subscription limit use
+/// `SubscribeOptions::with_handler_timeout` instead.
+pub fn timeout_middleware(timeout: Duration) -> MiddlewareFn {
+    MiddlewareFn::new("timeout", move |op, inner| {
+        if &*op == "publish" {
+            return inner;
+        }
+        Arc::new(move |msg: async_nats::Message| {
+            let inner = Arc::clone(&inner);
+            Box::pin(async move {
+                let subject = msg.subject.to_string();
+                with_timeout(&subject, timeout, inner(msg)).await
+            })
+        })
+    })
+}
+
+/// Built-in: metrics middleware.
+///
+/// Records, labelled by `operation` and `subject`:
+///   - published / consumed message counts
+///   - publish and handler latency
+///   - error counts
+///   - payload sizes
+///   - delivery lag (now − `start_time` header) for inbound messages
+///
+/// Place it after `tracing_middleware` so latency covers the inner chain
+/// only.
+pub fn metrics_middleware() -> MiddlewareFn {
+    MiddlewareFn::new("metrics", |op, inner| {
+        Arc::new(move |msg: async_nats::Message| {
+            let inner = Arc::clone(&inner);
+            let publish = op.as_ref() == "publish";
+            let attributes = vec![
+                KeyValue::new("operation", op.to_string()),
+                KeyValue::new("subject", msg.subject.to_string()),
+            ];
+
+            PAYLOAD_SIZE.record(msg.payload.len() as f64, &attributes);
+            if publish {
+                PUBLISHED_COUNT.add(1, &attributes);
+            } else {
+                CONSUMED_COUNT.add(1, &attributes);
+                if let Some(lag) = delivery_lag(&msg) {
+                    DELIVERY_LAG.record(lag, &attributes);
+                }
+            }
+
+            Box::pin(async move {
+                let start_time = std::time::Instant::now();
+                let result = inner(msg).await;
+                HANDLER_LATENCY.record(start_time.elapsed().as_secs_f64(), &attributes);
+                if result.is_err() {
+                    ERROR_COUNT.add(1, &attributes);
+                }
+                result
             }) as Pin<Box<dyn Future<Output = Result<(), MessagingError>> + Send>>
         })
     })
 }
+
+/// Seconds since the message's `start_time` header, if present and valid.
+fn delivery_lag(msg: &async_nats::Message) -> Option<f64> {
+    let sent = msg.headers.as_ref()?.get(HEADER_START_TIME)?.as_str();
+    let sent = chrono::DateTime::parse_from_rfc3339(sent).ok()?;
+    let lag = chrono::Utc::now().signed_duration_since(sent);
+    lag.to_std().ok().map(|d| d.as_secs_f64())
+}
+
+/// Built-in: retry + dead-letter middleware.
+///
+/// Re-runs a failing inbound handler up to `policy.max_attempts` times with
+/// exponential backoff and jitter (JetStream deliveries are kept alive with
+/// in-progress acks while waiting). Errors that are not
+/// `MessagingError::is_retryable` (e.g. undecodable payloads) skip straight
+/// to the dead letter. After the final failure the original
+/// payload and headers, plus `dlq_subject`/`dlq_reason`/`dlq_attempts`, are
+/// published to `<dead_letter_prefix>.<subject>` via `dead_letter` and the
+/// message counts as handled. If that publish fails the error is returned,
+/// so a JetStream delivery is nak'd rather than lost.
+///
+/// Pass a `NatsClient::persistent` handle as `dead_letter`, with the
+/// dead-letter subjects (`dlq.>`) captured by the stream, so the dead letter
+/// is stored before the original message is acked.
+///
+/// Publishes and requests (messages with a reply subject) pass through:
+/// the requester already received the error reply.
+pub fn retry_middleware(
+    policy: RetryConfig,
+    dead_letter: Option<Arc<dyn Publisher>>,
+) -> MiddlewareFn {
+    let policy = Arc::new(policy);
+    MiddlewareFn::new("retry", move |op, inner| {
+        if op.as_ref() == "publish" {
+            return inner;
+        }
+        let policy = Arc::clone(&policy);
+        let dead_letter = dead_letter.clone();
+        Arc::new(move |msg: async_nats::Message| {
+            let inner = Arc::clone(&inner);
+            let policy = Arc::clone(&policy);
+            let dead_letter = dead_letter.clone();
+            let op = Arc::clone(&op);
+
+            Box::pin(async move {
+                if msg.reply.is_some() {
+                    return inner(msg).await;
+                }
+
+                let subject = msg.subject.to_string();
+                let labels = [
+                    KeyValue::new("operation", op.to_string()),
+                    KeyValue::new("subject", subject.clone()),
+                ];
+
+                let mut attempt = 1;
+                let err = loop {
+                    match inner(msg.clone()).await {
+                        Ok(()) => return Ok(()),
+                        Err(e) if attempt >= policy.max_attempts => break e,
+                        Err(e) if !e.is_retryable() => {
+                            tracing::warn!(subject, error = %e, "NATS: handler failed, not retryable");
+                            break e;
+                        }
+                        Err(e) => {
+                            let delay = retry_backoff(&policy, attempt);
+                            tracing::warn!(
+                                subject,
+                                attempt,
+                                error = %e,
+                                delay_ms = delay.as_millis() as u64,
+                                "NATS: handler failed, retrying"
+                            );
+                            RETRY_COUNT.add(1, &labels);
+                            let _ = AckHandle::current().send(AckKind::InProgress).await;
+                            tokio::time::sleep(delay).await;
+                            attempt += 1;
+                        }
+                    }
+                };
+
+                let Some(publisher) = dead_letter.filter(|_| !policy.dead_letter_prefix.is_empty())
+                else {
+                    return Err(err);
+                };
+
+                let dlq_subject = format!("{}.{}", policy.dead_letter_prefix, subject);
+                let mut attrs = msg
+                    .headers
+                    .as_ref()
+                    .map(headers_to_attrs)
+                    .unwrap_or_default();
+                attrs.insert(HEADER_DLQ_SUBJECT.to_string(), subject.clone());
+                attrs.insert(
+                    HEADER_DLQ_REASON.to_string(),
+                    header_text(&err.to_string(), MAX_ERROR_HEADER_LEN),
+                );
+                attrs.insert(HEADER_DLQ_ATTEMPTS.to_string(), attempt.to_string());
+                // `subject` already names the partition; don't shard again.
+                attrs.remove(HEADER_PARTITION_KEY);
+
+                match publisher.publish(&dlq_subject, msg.payload, attrs).await {
+                    Ok(()) => {
+                        DEAD_LETTER_COUNT.add(1, &labels);
+                        tracing::error!(
+                            subject,
+                            dlq_subject,
+                            attempts = attempt,
+                            error = %err,
+                            "NATS: message dead-lettered"
+                        );
+                        Ok(())
+                    }
+                    Err(e) => {
+                        tracing::error!(subject, error = %e, "NATS: dead-letter publish failed");
+                        Err(err)
+                    }
+                }
+            }) as Pin<Box<dyn Future<Output = Result<(), MessagingError>> + Send>>
+        })
+    })
+}
+
+/// Built-in: idempotent consumer middleware.
+///
+/// Claims `<scope>:<subject>:<Nats-Msg-Id>` in `store` for `lease` before
+/// running the handler, and marks it done (remembered for `ttl`) once the
+/// handler succeeds. Messages already done are skipped (and acked); a
+/// message another delivery is still processing fails with a retryable
+/// 503 so it is nak'd and comes back later.
+///
+/// A failed or cancelled handler (timeout, drain deadline) releases its
+/// claim so the redelivery is processed; after a crash the lease runs
+/// out instead, so keep `lease` above the handler timeout. Place this
+/// outside `retry_middleware` so a dead-lettered message also counts as
+/// handled.
+///
+/// `scope` names the consuming service: different consumers of the same
+/// subject de-duplicate independently. Publishes, requests and messages
+/// without a message id pass through.
+pub fn idempotency_middleware(
+    store: Arc<dyn IdempotencyStore>,
+    scope: impl Into<String>,
+    ttl: Duration,
+    lease: Duration,
+) -> MiddlewareFn {
+    let scope: Arc<str> = Arc::from(scope.into());
+    MiddlewareFn::new("idempotency", move |op, inner| {
+        if op.as_ref() == "publish" {
+            return inner;
+        }
+        let store = Arc::clone(&store);
+        let scope = Arc::clone(&scope);
+        Arc::new(move |msg: async_nats::Message| {
+            let inner = Arc::clone(&inner);
+            let store = Arc::clone(&store);
+            let op = Arc::clone(&op);
+
+            let message_id = msg
+                .headers
+                .as_ref()
+                .and_then(|h| h.get(HEADER_MESSAGE_ID))
+                .map(|v| v.as_str().to_string());
+            let key = message_id
+                .filter(|_| msg.reply.is_none())
+                .map(|id| format!("{}:{}:{}", scope, msg.subject, id));
+
+            Box::pin(async move {
+                let Some(key) = key else {
+                    return inner(msg).await;
+                };
+
+                let subject = msg.subject.to_string();
+                match store.try_claim(&key, lease).await? {
+                    Claim::Acquired => {}
+                    Claim::InProgress => {
+                        tracing::debug!(
+                            subject,
+                            key,
+                            "NATS: message is being processed, retry later"
+                        );
+                        return Err(MessagingError::service(
+                            503,
+                            "message is being processed by another delivery",
+                        ));
+                    }
+                    Claim::Done => {
+                        tracing::debug!(subject, key, "NATS: duplicate message skipped");
+                        DUPLICATE_COUNT.add(
+                            1,
+                            &[
+                                KeyValue::new("operation", op.to_string()),
+                                KeyValue::new("subject", subject),
+                            ],
+                        );
+                        return Ok(());
+                    }
+                }
+
+                let claim = ClaimGuard {
+                    store: Arc::clone(&store),
+                    key: Some(key.clone()),
+                };
+                let result = inner(msg).await;
+                claim.disarm();
+                let settled = match &result {
+                    Ok(()) => store.complete(&key, ttl).await,
+                    Err(_) => store.release(&key).await,
+                };
+                if let Err(e) = settled {
+                    tracing::warn!(key, error = %e, "NATS: failed to settle idempotency key");
+                }
+                result
+            }) as Pin<Box<dyn Future<Output = Result<(), MessagingError>> + Send>>
+        })
+    })
+}
+
+/// Releases an idempotency claim if the handler is dropped before it
+/// finishes, so the nak'd redelivery is not taken for a duplicate.
+struct ClaimGuard {
+    store: Arc<dyn IdempotencyStore>,
+    key: Option<String>,
+}
+
+impl ClaimGuard {
+    fn disarm(mut self) {
+        self.key = None;
+    }
+}
+
+impl Drop for ClaimGuard {
+    fn drop(&mut self) {
+        let (Some(key), Ok(runtime)) = (self.key.take(), tokio::runtime::Handle::try_current())
+        else {
+            return;
+        };
+        let store = Arc::clone(&self.store);
+        runtime.spawn(async move {
+            if let Err(e) = store.release(&key).await {
+                tracing::warn!(key, error = %e, "NATS: failed to release idempotency key");
+            }
+        });
+    }
+}
+
+/// `initial * multiplier^(attempt-1)`, +/- jitter, capped at `max_backoff`.
+fn retry_backoff(policy: &RetryConfig, attempt: u32) -> Duration {
+    let base = policy.initial_backoff().as_secs_f64()
+        * policy
+            .multiplier
+            .max(1.0)
+            .powi(attempt.saturating_sub(1).min(i32::MAX as u32) as i32);
+    let jitter = policy.jitter.clamp(0.0, 1.0);
+    let factor = 1.0 + rand::random_range(-jitter..=jitter);
+    let delay = (base * factor).min(policy.max_backoff().as_secs_f64());
+    Duration::from_secs_f64(delay.max(0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::memory::MemoryIdempotencyStore;
+
+    const TTL: Duration = Duration::from_secs(60);
+    const LEASE: Duration = Duration::from_secs(60);
+
+    fn message(id: &str) -> async_nats::Message {
+        let mut headers = async_nats::HeaderMap::new();
+        headers.insert(HEADER_MESSAGE_ID, id);
+        async_nats::Message {
+            subject: "user.created".into(),
+            reply: None,
+            payload: Bytes::new(),
+            headers: Some(headers),
+            status: None,
+            description: None,
+            length: 0,
+        }
+    }
+
+    /// Idempotent handler chain counting completed calls; with `hang_first`
+    /// the first call never finishes.
+    fn counting(completed: Arc<AtomicUsize>, hang_first: bool) -> NatsHandlerFn {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let handler: NatsHandlerFn = Arc::new(move |_msg| {
+            let calls = Arc::clone(&calls);
+            let completed = Arc::clone(&completed);
+            Box::pin(async move {
+                if hang_first && calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                    std::future::pending::<()>().await;
+                }
+                completed.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            })
+        });
+        let store = Arc::new(MemoryIdempotencyStore::new());
+        apply_middleware(
+            "durable_subscribe",
+            handler,
+            &[idempotency_middleware(store, "worker", TTL, LEASE)],
+        )
+    }
+
+    #[tokio::test]
+    async fn timed_out_message_is_processed_on_redelivery() {
+        let completed = Arc::new(AtomicUsize::new(0));
+        let chain = counting(Arc::clone(&completed), true);
+
+        let first = with_timeout(
+            "user.created",
+            Duration::from_millis(20),
+            chain(message("m-1")),
+        )
+        .await;
+        assert!(matches!(first, Err(MessagingError::Timeout(_))));
+
+        // The claim is released in the background; redeliver until it is.
+        let mut redelivered = Err(MessagingError::Closed);
+        for _ in 0..50 {
+            redelivered = chain(message("m-1")).await;
+            if redelivered.is_ok() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        redelivered.unwrap();
+        assert_eq!(completed.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn processed_message_is_skipped_on_redelivery() {
+        let completed = Arc::new(AtomicUsize::new(0));
+        let chain = counting(Arc::clone(&completed), false);
+
+        chain(message("m-1")).await.unwrap();
+        chain(message("m-1")).await.unwrap();
+        assert_eq!(completed.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn retry_backoff_grows_and_stays_within_bounds() {
+        let policy = RetryConfig {
+            initial_backoff_ms: 100,
+            max_backoff_ms: 1_000,
+            multiplier: 2.0,
+            jitter: 0.2,
+            ..RetryConfig::default()
+        };
+        for _ in 0..200 {
+            let first = retry_backoff(&policy, 1);
+            assert!(first >= Duration::from_millis(80) && first <= Duration::from_millis(120));
+            let third = retry_backoff(&policy, 3);
+            assert!(third >= Duration::from_millis(320) && third <= Duration::from_millis(480));
+            // Jitter never pushes a capped delay past max_backoff.
+            let late = retry_backoff(&policy, 10);
+            assert!(late >= Duration::from_millis(800) && late <= policy.max_backoff());
+            assert_eq!(retry_backoff(&policy, u32::MAX), policy.max_backoff());
+        }
+    }
+
+    #[test]
+    fn retry_backoff_without_jitter_is_exact() {
+        let policy = RetryConfig {
+            initial_backoff_ms: 100,
+            max_backoff_ms: 250,
+            multiplier: 2.0,
+            jitter: 0.0,
+            ..RetryConfig::default()
+        };
+        let delays: Vec<_> = (0..=4)
+            .map(|attempt| retry_backoff(&policy, attempt))
+            .collect();
+        assert_eq!(delays, [100, 100, 200, 250, 250].map(Duration::from_millis),);
+
+        // A multiplier below 1 does not shrink the delay.
+        let flat = RetryConfig {
+            multiplier: 0.5,
+            ..policy
+        };
+        assert_eq!(retry_backoff(&flat, 3), Duration::from_millis(100));
+    }
+}
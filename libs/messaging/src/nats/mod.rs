@@ -1,6 +1,14 @@
+pub mod ack;
 pub mod client;
 pub mod factory;
 pub mod headers;
+pub mod jetstream;
+pub mod kv;
 pub mod middleware;
+pub mod object_store;
+pub mod status;
 
 pub use client::NatsClient;
+pub use kv::NatsKeyValue;
+pub use object_store::NatsObjectStore;
+pub use status::{ConnectionState, ConnectionStatus};
@@ -0,0 +1,132 @@
+use async_nats::jetstream::object_store::{
+    self, GetErrorKind, InfoErrorKind, ObjectInfo as NatsObjectInfo, ObjectMetadata,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
+use tokio::io::AsyncRead;
+
+use crate::{
+    MessagingError,
+    kv::BucketOptions,
+    nats::kv::{is_bucket_not_found, storage_type, store_err},
+    object_store::{ObjectInfo, ObjectMeta, ObjectReader, ObjectStore},
+};
+
+use super::NatsClient;
+
+/// A JetStream object-store bucket.
+#[derive(Clone)]
+pub struct NatsObjectStore {
+    store: object_store::ObjectStore,
+    bucket: String,
+}
+
+impl std::fmt::Debug for NatsObjectStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NatsObjectStore")
+            .field("bucket", &self.bucket)
+            .finish()
+    }
+}
+
+impl NatsClient {
+    /// Open the object-store bucket `options.bucket`, creating it with
+    /// `options` if it does not exist yet. `history` and `per_key_ttl` do
+    /// not apply to object stores.
+    pub async fn object_store(
+        &self,
+        options: &BucketOptions,
+    ) -> Result<NatsObjectStore, MessagingError> {
+        let store = match self.jetstream.get_object_store(&options.bucket).await {
+            Ok(store) => store,
+            Err(e) if !is_bucket_not_found(&e) => return Err(store_err(e)),
+            Err(_) => self
+                .jetstream
+                .create_object_store(object_store::Config {
+                    bucket: options.bucket.clone(),
+                    max_age: options.max_age.unwrap_or_default(),
+                    storage: storage_type(options),
+                    num_replicas: options.replicas,
+                    ..Default::default()
+                })
+                .await
+                .map_err(store_err)?,
+        };
+        Ok(NatsObjectStore {
+            store,
+            bucket: options.bucket.clone(),
+        })
+    }
+}
+
+fn to_info(info: NatsObjectInfo) -> ObjectInfo {
+    ObjectInfo {
+        name: info.name,
+        description: info.description,
+        metadata: info.metadata,
+        size: info.size,
+        chunks: info.chunks,
+        digest: info.digest,
+        modified: info
+            .modified
+            .and_then(|t| DateTime::<Utc>::from_timestamp(t.unix_timestamp(), t.nanosecond())),
+    }
+}
+
+#[async_trait]
+impl ObjectStore for NatsObjectStore {
+    async fn put(
+        &self,
+        meta: ObjectMeta,
+        mut data: &mut (dyn AsyncRead + Send + Unpin),
+    ) -> Result<ObjectInfo, MessagingError> {
+        let meta = ObjectMetadata {
+            name: meta.name,
+            description: meta.description,
+            chunk_size: meta.chunk_size,
+            metadata: meta.metadata,
+            headers: None,
+        };
+        self.store
+            .put(meta, &mut data)
+            .await
+            .map(to_info)
+            .map_err(store_err)
+    }
+
+    async fn get(&self, name: &str) -> Result<Option<(ObjectInfo, ObjectReader)>, MessagingError> {
+        match self.store.get(name).await {
+            Ok(object) => {
+                let info = to_info(object.info().clone());
+                Ok(Some((info, Box::new(object))))
+            }
+            Err(e) if e.kind() == GetErrorKind::NotFound => Ok(None),
+            Err(e) => Err(store_err(e)),
+        }
+    }
+
+    async fn info(&self, name: &str) -> Result<Option<ObjectInfo>, MessagingError> {
+        match self.store.info(name).await {
+            Ok(info) if info.deleted => Ok(None),
+            Ok(info) => Ok(Some(to_info(info))),
+            Err(e) if e.kind() == InfoErrorKind::NotFound => Ok(None),
+            Err(e) => Err(store_err(e)),
+        }
+    }
+
+    async fn delete(&self, name: &str) -> Result<(), MessagingError> {
+        self.store.delete(name).await.map_err(store_err)
+    }
+
+    async fn list(&self) -> Result<Vec<ObjectInfo>, MessagingError> {
+        self.store
+            .list()
+            .await
+            .map_err(store_err)?
+            .map_ok(to_info)
+            .map_err(store_err)
+            .try_collect()
+            .await
+    }
+}
@@ -0,0 +1,154 @@
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use opentelemetry::KeyValue;
+use serde::Serialize;
+
+use ro_telemetry::meter::messaging::{CONNECTION_STATE, RECONNECT_COUNT};
+
+/// Connection state as seen through client events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionState {
+    /// Connecting for the first time.
+    Connecting,
+    Connected,
+    /// Lost; the client is reconnecting (if allowed).
+    Disconnected,
+    /// Drained or closed; the client will not reconnect.
+    Closed,
+}
+
+/// Snapshot returned by `NatsClient::status()`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionStatus {
+    pub state: ConnectionState,
+    /// When `state` was entered.
+    pub since: DateTime<Utc>,
+    /// Successful reconnects since start.
+    pub reconnects: u64,
+    /// Most recent client or server error.
+    pub last_error: Option<String>,
+    /// The server announced lame duck mode (it is about to shut down).
+    pub lame_duck: bool,
+}
+
+impl ConnectionStatus {
+    /// `true` while connected and the server is not shutting down.
+    pub fn is_healthy(&self) -> bool {
+        self.state == ConnectionState::Connected && !self.lame_duck
+    }
+}
+
+/// Folds `async_nats::Event`s into a `ConnectionStatus`, logging each one
+/// and updating the connection-state gauge.
+#[derive(Debug)]
+pub(crate) struct StatusTracker {
+    name: String,
+    status: Mutex<ConnectionStatus>,
+}
+
+impl StatusTracker {
+    pub(crate) fn new(name: String) -> Self {
+        Self {
+            name,
+            status: Mutex::new(ConnectionStatus {
+                state: ConnectionState::Connecting,
+                since: Utc::now(),
+                reconnects: 0,
+                last_error: None,
+                lame_duck: false,
+            }),
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> ConnectionStatus {
+        match self.status.lock() {
+            Ok(status) => status.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    pub(crate) fn on_event(&self, event: async_nats::Event) {
+        let name = self.name.as_str();
+        match &event {
+            async_nats::Event::Connected => {
+                let reconnected = self.snapshot().state == ConnectionState::Disconnected;
+                if reconnected {
+                    tracing::info!(name, "NATS: reconnected");
+                    RECONNECT_COUNT.add(1, &[KeyValue::new("client", self.name.clone())]);
+                } else {
+                    tracing::info!(name, "NATS: connected");
+                }
+                self.update(|s| {
+                    if reconnected {
+                        s.reconnects += 1;
+                    }
+                    s.lame_duck = false;
+                    Some(ConnectionState::Connected)
+                });
+            }
+            async_nats::Event::Disconnected => {
+                tracing::warn!(name, "NATS: disconnected");
+                self.update(|_| Some(ConnectionState::Disconnected));
+            }
+            async_nats::Event::Closed => {
+                tracing::info!(name, "NATS: connection closed");
+                self.update(|_| Some(ConnectionState::Closed));
+            }
+            async_nats::Event::Draining => {
+                tracing::info!(name, "NATS: draining connection");
+            }
+            async_nats::Event::LameDuckMode => {
+                tracing::warn!(name, "NATS: server entered lame duck mode");
+                self.update(|s| {
+                    s.lame_duck = true;
+                    None
+                });
+            }
+            async_nats::Event::SlowConsumer(sid) => {
+                tracing::warn!(name, sid, "NATS: slow consumer, messages dropped");
+            }
+            async_nats::Event::ServerError(err) => {
+                tracing::error!(name, error = %err, "NATS: server error");
+                self.record_error(event.to_string());
+            }
+            async_nats::Event::ClientError(err) => {
+                tracing::error!(name, error = %err, "NATS: client error");
+                self.record_error(event.to_string());
+            }
+        }
+    }
+
+    pub(crate) fn set_state(&self, state: ConnectionState) {
+        self.update(|_| Some(state));
+    }
+
+    /// Move to `state` only if the current state is still `current`, so a
+    /// client event that arrived meanwhile is not overwritten.
+    pub(crate) fn transition(&self, current: ConnectionState, state: ConnectionState) {
+        self.update(|s| (s.state == current).then_some(state));
+    }
+
+    fn record_error(&self, error: String) {
+        self.update(|s| {
+            s.last_error = Some(error);
+            None
+        });
+    }
+
+    /// Apply `f`; a returned state becomes current (and is reported).
+    fn update(&self, f: impl FnOnce(&mut ConnectionStatus) -> Option<ConnectionState>) {
+        let Ok(mut status) = self.status.lock() else {
+            return;
+        };
+        if let Some(state) = f(&mut status)
+            && state != status.state
+        {
+            status.state = state;
+            status.since = Utc::now();
+            let up = i64::from(state == ConnectionState::Connected);
+            CONNECTION_STATE.record(up, &[KeyValue::new("client", self.name.clone())]);
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use std::{collections::HashMap, fmt::Debug};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use tokio::io::AsyncRead;
+
+use crate::MessagingError;
+
+/// Streamed object content.
+pub type ObjectReader = Box<dyn AsyncRead + Send + Unpin>;
+
+/// What to store alongside an object's content.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ObjectMeta {
+    pub name: String,
+    pub description: Option<String>,
+    /// Free-form key/value pairs returned with the object.
+    pub metadata: HashMap<String, String>,
+    /// Upload chunk size in bytes; the store default (128 KiB) when `None`.
+    pub chunk_size: Option<usize>,
+}
+
+impl ObjectMeta {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = Some(chunk_size);
+        self
+    }
+}
+
+/// A stored object, without its content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectInfo {
+    pub name: String,
+    pub description: Option<String>,
+    pub metadata: HashMap<String, String>,
+    /// Size in bytes.
+    pub size: usize,
+    pub chunks: usize,
+    /// `SHA-256=<base64url>` of the content, when the store computes one.
+    pub digest: Option<String>,
+    pub modified: Option<DateTime<Utc>>,
+}
+
+/// A bucket of named blobs of any size.
+///
+/// Content is streamed in and out in chunks, so objects never have to fit
+/// in one message (or in memory). Writing an existing name replaces it.
+#[async_trait]
+pub trait ObjectStore: Send + Sync + Debug {
+    /// Upload everything `data` yields under `meta.name`.
+    async fn put(
+        &self,
+        meta: ObjectMeta,
+        data: &mut (dyn AsyncRead + Send + Unpin),
+    ) -> Result<ObjectInfo, MessagingError>;
+
+    /// Stream the content of `name`; `None` if it does not exist.
+    async fn get(&self, name: &str) -> Result<Option<(ObjectInfo, ObjectReader)>, MessagingError>;
+
+    async fn info(&self, name: &str) -> Result<Option<ObjectInfo>, MessagingError>;
+
+    async fn delete(&self, name: &str) -> Result<(), MessagingError>;
+
+    async fn list(&self) -> Result<Vec<ObjectInfo>, MessagingError>;
+}
@@ -0,0 +1,577 @@
+//! Ordered processing by partition key.
+//!
+//! Messages published with a `partition-key` attr (`Message::with_partition_key`)
+//! go to `<topic>.<n>`, where `n` is a consistent hash of the key, so all
+//! messages for one key share a subject. A `PartitionedConsumer` per
+//! instance subscribes to the partitions assigned to it, one ordered
+//! durable consumer each: a partition is processed sequentially while
+//! partitions run in parallel.
+//!
+//! Keyed messages are never published on the unsharded topic itself, so a
+//! plain subscriber of `<topic>` no longer receives them; subscribe to
+//! `<topic>.*` to see every partition (without ordering guarantees).
+//!
+//! Consumers of the same topic and group register in a KV bucket and split
+//! the partitions among the live members (rendezvous hashing). Each
+//! partition is guarded by a `DistributedLock`, and a departing owner stops
+//! its subscription before releasing the lock, so a partition never has two
+//! owners during a rebalance.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use ro_config::config::nats::PartitioningConfig;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::{Notify, watch},
+    task::JoinHandle,
+};
+
+use crate::{
+    DurableSubscriber, Handler, KeyValueStore, MessagingError,
+    error::IoOp,
+    lock::{DistributedLock, Lease},
+};
+
+/// Partition of `key` among `partitions` (jump consistent hash of the
+/// key's FNV-1a hash). Growing the partition count moves as few keys as
+/// possible.
+pub fn partition_for(key: &str, partitions: u32) -> u32 {
+    let mut hash = fnv1a(key.as_bytes());
+    let (mut bucket, mut next) = (0_i64, 0_i64);
+    while next < i64::from(partitions.max(1)) {
+        bucket = next;
+        hash = hash.wrapping_mul(2_862_933_555_777_941_757).wrapping_add(1);
+        next = ((bucket + 1) as f64 * ((1_i64 << 31) as f64 / ((hash >> 33) + 1) as f64)) as i64;
+    }
+    bucket as u32
+}
+
+/// Subject of `partition` of `topic`.
+pub fn subject(topic: &str, partition: u32) -> String {
+    format!("{topic}.{partition}")
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Member that owns `partition`: the one with the highest hash of
+/// `(member, partition)`, so every member computes the same assignment and
+/// a join or leave only moves that member's share.
+fn owner(members: &BTreeSet<String>, partition: u32) -> Option<&str> {
+    members
+        .iter()
+        .max_by_key(|m| mix(fnv1a(format!("{m}/{partition}").as_bytes())))
+        .map(String::as_str)
+}
+
+/// Finalizer (MurmurHash3 `fmix64`): FNV-1a of strings that differ only in
+/// the last bytes barely differs in the high bits, which would skew the
+/// highest-weight comparison.
+fn mix(mut hash: u64) -> u64 {
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^ (hash >> 33)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Heartbeat {
+    expires_at: DateTime<Utc>,
+}
+
+/// Consumes the partitions of `topic` assigned to this instance.
+///
+/// `subscriber` should be an ordered handle
+/// (`with_subscribe_options(SubscribeOptions::ordered())`), so each
+/// partition is handled one message at a time.
+///
+/// ```rust,ignore
+/// let partitions = PartitionedConsumer::new(
+///     Arc::new(nats.with_subscribe_options(options.ordered())),
+///     kv, "user.created", "worker", instance_id, &partitioning,
+/// )?
+/// .start(topics::USER_CREATED.handler(on_user_created));
+/// // on shutdown
+/// partitions.stop().await;
+/// ```
+pub struct PartitionedConsumer {
+    subscriber: Arc<dyn DurableSubscriber>,
+    store: Arc<dyn KeyValueStore>,
+    topic: String,
+    group: String,
+    member: String,
+    partitions: u32,
+    ttl: Duration,
+}
+
+/// A running `PartitionedConsumer`.
+#[derive(Debug)]
+pub struct PartitionHandle {
+    owned: watch::Receiver<Vec<u32>>,
+    stop: Arc<Notify>,
+    task: JoinHandle<()>,
+}
+
+impl std::fmt::Debug for PartitionedConsumer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PartitionedConsumer")
+            .field("topic", &self.topic)
+            .field("group", &self.group)
+            .field("member", &self.member)
+            .field("partitions", &self.partitions)
+            .field("ttl", &self.ttl)
+            .finish()
+    }
+}
+
+impl PartitionedConsumer {
+    /// `member` must be unique per instance and must not contain `.`;
+    /// instances sharing `group` split the partitions.
+    pub fn new(
+        subscriber: Arc<dyn DurableSubscriber>,
+        store: Arc<dyn KeyValueStore>,
+        topic: impl Into<String>,
+        group: impl Into<String>,
+        member: impl Into<String>,
+        cfg: &PartitioningConfig,
+    ) -> Result<Self, MessagingError> {
+        let member = member.into();
+        // The member is the last token of its heartbeat key.
+        if member.is_empty() || member.contains('.') {
+            return Err(MessagingError::io(
+                IoOp::Subscribe,
+                format!(
+                    "invalid partition member {member:?}: must be a non-empty token without '.'"
+                ),
+            ));
+        }
+        Ok(Self {
+            subscriber,
+            store,
+            topic: topic.into(),
+            group: group.into(),
+            member,
+            partitions: cfg.partitions.max(1),
+            ttl: cfg.ttl(),
+        })
+    }
+
+    /// Join the group and rebalance in the background every third of the
+    /// TTL.
+    pub fn start(self, handler: Handler) -> PartitionHandle {
+        let (tx, owned) = watch::channel(Vec::new());
+        let stop = Arc::new(Notify::new());
+        let stopped = Arc::clone(&stop);
+        let task = tokio::spawn(self.run(handler, tx, stopped));
+        PartitionHandle { owned, stop, task }
+    }
+
+    async fn run(self, handler: Handler, tx: watch::Sender<Vec<u32>>, stop: Arc<Notify>) {
+        let mut owned: BTreeMap<u32, Lease> = BTreeMap::new();
+        loop {
+            if let Err(e) = self.heartbeat().await {
+                tracing::warn!(topic = %self.topic, error = %e, "partitions: heartbeat failed");
+            }
+            match self.members().await {
+                Ok(members) => self.rebalance(&members, &mut owned, &handler).await,
+                Err(e) => {
+                    tracing::warn!(topic = %self.topic, error = %e, "partitions: listing members failed");
+                }
+            }
+            self.renew(&mut owned).await;
+            tx.send_if_modified(|current| {
+                let now: Vec<u32> = owned.keys().copied().collect();
+                std::mem::replace(current, now.clone()) != now
+            });
+
+            tokio::select! {
+                _ = stop.notified() => break,
+                _ = tokio::time::sleep(self.ttl / 3) => {}
+            }
+        }
+
+        // Leave: free every partition and drop out of the member list so
+        // the others take over at once.
+        for (partition, lease) in std::mem::take(&mut owned) {
+            self.revoke(partition, Some(lease)).await;
+        }
+        tx.send_replace(Vec::new());
+        if let Err(e) = self
+            .store
+            .delete(&self.member_key(&self.member), None)
+            .await
+        {
+            tracing::warn!(topic = %self.topic, error = %e, "partitions: leaving group failed");
+        }
+    }
+
+    /// Take the partitions now assigned to this member and give up the
+    /// others.
+    async fn rebalance(
+        &self,
+        members: &BTreeSet<String>,
+        owned: &mut BTreeMap<u32, Lease>,
+        handler: &Handler,
+    ) {
+        let assigned: BTreeSet<u32> = (0..self.partitions)
+            .filter(|p| owner(members, *p) == Some(self.member.as_str()))
+            .collect();
+
+        let moved: Vec<u32> = owned
+            .keys()
+            .filter(|p| !assigned.contains(p))
+            .copied()
+            .collect();
+        for partition in moved {
+            let lease = owned.remove(&partition);
+            self.revoke(partition, lease).await;
+        }
+
+        for partition in assigned {
+            if owned.contains_key(&partition) {
+                continue;
+            }
+            // The previous owner may still be finishing; retried next tick.
+            let lease = match self.lock(partition).try_acquire().await {
+                Ok(Some(lease)) => lease,
+                Ok(None) => continue,
+                Err(e) => {
+                    tracing::warn!(topic = %self.topic, partition, error = %e, "partitions: lock failed");
+                    continue;
+                }
+            };
+            match self.subscribe(partition, handler).await {
+                Ok(()) => {
+                    tracing::info!(topic = %self.topic, partition, "partitions: assigned");
+                    owned.insert(partition, lease);
+                }
+                Err(e) => {
+                    tracing::warn!(topic = %self.topic, partition, error = %e, "partitions: subscribe failed");
+                    let _ = self.lock(partition).release(lease).await;
+                }
+            }
+        }
+    }
+
+    /// Extend the leases of owned partitions, stopping those whose lease
+    /// was lost.
+    async fn renew(&self, owned: &mut BTreeMap<u32, Lease>) {
+        let partitions: Vec<u32> = owned.keys().copied().collect();
+        for partition in partitions {
+            let Some(held) = owned.remove(&partition) else {
+                continue;
+            };
+            match self.lock(partition).renew(&held).await {
+                Ok(renewed) => {
+                    owned.insert(partition, renewed);
+                }
+                Err(e) if e.is_retryable() && held.expires_at > Utc::now() => {
+                    tracing::warn!(topic = %self.topic, partition, error = %e, "partitions: renew failed");
+                    owned.insert(partition, held);
+                }
+                Err(e) => {
+                    tracing::warn!(topic = %self.topic, partition, error = %e, "partitions: lease lost");
+                    self.revoke(partition, None).await;
+                }
+            }
+        }
+    }
+
+    /// Stop consuming `partition`, then release its lock. Unsubscribing
+    /// waits for the running handler, so the next owner starts after it.
+    async fn revoke(&self, partition: u32, lease: Option<Lease>) {
+        let topic = subject(&self.topic, partition);
+        if let Err(e) = self.subscriber.unsubscribe(&topic).await {
+            tracing::warn!(topic, error = %e, "partitions: unsubscribe failed");
+        }
+        if let Some(lease) = lease {
+            let _ = self.lock(partition).release(lease).await;
+        }
+        tracing::info!(topic = %self.topic, partition, "partitions: revoked");
+    }
+
+    async fn subscribe(&self, partition: u32, handler: &Handler) -> Result<(), MessagingError> {
+        // Consumer names may not contain `.`.
+        let durable = format!(
+            "{}-{}-{partition}",
+            self.group,
+            self.topic.replace(['.', '*', '>'], "_")
+        );
+        self.subscriber
+            .durable_subscribe(
+                &subject(&self.topic, partition),
+                &durable,
+                Arc::clone(handler),
+            )
+            .await
+            .map(|_| ())
+    }
+
+    async fn heartbeat(&self) -> Result<(), MessagingError> {
+        let beat = Heartbeat {
+            expires_at: Utc::now() + self.ttl,
+        };
+        let value = serde_json::to_vec(&beat).map_err(MessagingError::encode)?;
+        self.store
+            .put(&self.member_key(&self.member), Bytes::from(value))
+            .await
+            .map(|_| ())
+    }
+
+    /// Live members of the group, this one included. Expired heartbeats
+    /// are deleted on the way.
+    async fn members(&self) -> Result<BTreeSet<String>, MessagingError> {
+        let prefix = self.member_key("");
+        let mut members = BTreeSet::from([self.member.clone()]);
+        for key in self.store.keys().await? {
+            let Some(member) = key.strip_prefix(&prefix).filter(|m| !m.contains('.')) else {
+                continue;
+            };
+            let Some(entry) = self.store.get(&key).await? else {
+                continue;
+            };
+            let alive = serde_json::from_slice::<Heartbeat>(&entry.value)
+                .is_ok_and(|beat| beat.expires_at > Utc::now());
+            if alive {
+                members.insert(member.to_string());
+            } else if let Err(e) = self.store.delete(&key, Some(entry.revision)).await {
+                // A conflict means the member came back in the meantime.
+                tracing::debug!(key, error = %e, "partitions: deleting stale member failed");
+            }
+        }
+        Ok(members)
+    }
+
+    /// Heartbeat key of `member`. The topic is hashed into one token, so
+    /// topics sharing a prefix (`user`, `user.created`) don't overlap.
+    fn member_key(&self, member: &str) -> String {
+        format!(
+            "members.{}.{:016x}.{member}",
+            self.group,
+            fnv1a(self.topic.as_bytes())
+        )
+    }
+
+    fn lock(&self, partition: u32) -> DistributedLock {
+        DistributedLock::new(
+            Arc::clone(&self.store),
+            format!("partitions.{}.{}.{partition}", self.group, self.topic),
+            self.member.clone(),
+            self.ttl,
+        )
+    }
+}
+
+impl PartitionHandle {
+    /// Partitions currently owned by this instance.
+    pub fn partitions(&self) -> watch::Receiver<Vec<u32>> {
+        self.owned.clone()
+    }
+
+    /// Leave the group: stop every partition subscription and release it
+    /// to the remaining members.
+    pub async fn stop(self) {
+        self.stop.notify_one();
+        let _ = self.task.await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    fn members(names: &[&str]) -> BTreeSet<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    fn assignment(members: &BTreeSet<String>, partitions: u32) -> Vec<String> {
+        (0..partitions)
+            .map(|p| owner(members, p).unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn partition_is_deterministic_and_in_range() {
+        for i in 0..1_000 {
+            let key = format!("user-{i}");
+            let partition = partition_for(&key, 16);
+            assert!(partition < 16);
+            assert_eq!(partition, partition_for(&key, 16));
+        }
+        // Pinned so a hashing change that would reshuffle live data fails.
+        assert_eq!(partition_for("user-1", 16), 12);
+        assert_eq!(partition_for("user-2", 16), 7);
+        assert_eq!(partition_for("abc", 1024), 922);
+        assert_eq!(partition_for("user-1", 1), 0);
+        assert_eq!(partition_for("user-1", 0), 0);
+    }
+
+    #[test]
+    fn keys_spread_evenly_over_partitions() {
+        let partitions = 16;
+        let keys = 16_000;
+        let mut counts = vec![0_usize; partitions as usize];
+        for i in 0..keys {
+            counts[partition_for(&format!("user-{i}"), partitions) as usize] += 1;
+        }
+        let expected = keys / partitions as usize;
+        for (partition, count) in counts.iter().enumerate() {
+            assert!(
+                count.abs_diff(expected) < expected / 5,
+                "partition {partition} got {count} keys, expected about {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn growing_partition_count_moves_few_keys() {
+        let keys = 10_000;
+        let moved = (0..keys)
+            .map(|i| format!("user-{i}"))
+            .filter(|key| partition_for(key, 16) != partition_for(key, 17))
+            .count();
+        // Ideal is keys / 17 (~588); every moved key goes to the new partition.
+        assert!(moved < keys / 10, "{moved} keys moved");
+        for i in 0..keys {
+            let key = format!("user-{i}");
+            let (before, after) = (partition_for(&key, 16), partition_for(&key, 17));
+            assert!(before == after || after == 16);
+        }
+    }
+
+    #[test]
+    fn assignment_is_deterministic_and_covers_every_partition() {
+        let group = members(&["w-1", "w-2", "w-3"]);
+        let first = assignment(&group, 64);
+        assert_eq!(first, assignment(&group, 64));
+        assert!(first.iter().all(|m| group.contains(m)));
+        for member in &group {
+            let share = first.iter().filter(|m| *m == member).count();
+            assert!(share > 0, "{member} got no partition");
+        }
+        assert_eq!(owner(&BTreeSet::new(), 0), None);
+    }
+
+    #[test]
+    fn member_joining_only_takes_partitions() {
+        let before = assignment(&members(&["w-1", "w-2", "w-3"]), 64);
+        let after = assignment(&members(&["w-1", "w-2", "w-3", "w-4"]), 64);
+        for (old, new) in before.iter().zip(&after) {
+            assert!(old == new || new == "w-4", "{old} -> {new}");
+        }
+        assert!(after.iter().any(|m| m == "w-4"));
+    }
+
+    #[test]
+    fn member_leaving_only_releases_its_partitions() {
+        let before = assignment(&members(&["w-1", "w-2", "w-3"]), 64);
+        let after = assignment(&members(&["w-1", "w-3"]), 64);
+        for (old, new) in before.iter().zip(&after) {
+            assert!(old == new || old == "w-2", "{old} -> {new}");
+        }
+    }
+
+    /// Subscriber for consumers that never get to subscribe.
+    #[derive(Debug)]
+    struct NoSubscriber;
+
+    #[async_trait::async_trait]
+    impl DurableSubscriber for NoSubscriber {
+        async fn durable_subscribe(
+            &self,
+            _topic: &str,
+            _durable: &str,
+            _handler: Handler,
+        ) -> Result<crate::SubscriptionId, MessagingError> {
+            Err(MessagingError::Closed)
+        }
+
+        async fn unsubscribe(&self, _topic: &str) -> Result<(), MessagingError> {
+            Ok(())
+        }
+
+        async fn close(&self) -> Result<(), MessagingError> {
+            Ok(())
+        }
+    }
+
+    fn consumer(store: &Arc<dyn KeyValueStore>, topic: &str, member: &str) -> PartitionedConsumer {
+        let cfg = PartitioningConfig {
+            partitions: 4,
+            bucket: "partitions".to_string(),
+            ttl_secs: 15,
+        };
+        PartitionedConsumer::new(
+            Arc::new(NoSubscriber),
+            Arc::clone(store),
+            topic,
+            "worker",
+            member,
+            &cfg,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn topics_sharing_a_prefix_have_separate_members() {
+        let store: Arc<dyn KeyValueStore> = Arc::new(crate::memory::MemoryKeyValue::new());
+        let user = consumer(&store, "user", "w-1");
+        let created = consumer(&store, "user.created", "w-2");
+        user.heartbeat().await.unwrap();
+        created.heartbeat().await.unwrap();
+
+        assert_eq!(user.members().await.unwrap(), members(&["w-1"]));
+        assert_eq!(created.members().await.unwrap(), members(&["w-2"]));
+    }
+
+    #[tokio::test]
+    async fn stale_members_are_dropped_and_deleted() {
+        let store: Arc<dyn KeyValueStore> = Arc::new(crate::memory::MemoryKeyValue::new());
+        let live = consumer(&store, "user.created", "w-1");
+        let gone = consumer(&store, "user.created", "w-2");
+        let beat = Heartbeat {
+            expires_at: Utc::now() - Duration::from_secs(1),
+        };
+        store
+            .put(
+                &gone.member_key("w-2"),
+                Bytes::from(serde_json::to_vec(&beat).unwrap()),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(live.members().await.unwrap(), members(&["w-1"]));
+        assert!(store.get(&gone.member_key("w-2")).await.unwrap().is_none());
+    }
+
+    #[test]
+    fn members_with_dots_are_rejected() {
+        let store: Arc<dyn KeyValueStore> = Arc::new(crate::memory::MemoryKeyValue::new());
+        let cfg = PartitioningConfig {
+            partitions: 4,
+            bucket: "partitions".to_string(),
+            ttl_secs: 15,
+        };
+        let result = PartitionedConsumer::new(
+            Arc::new(NoSubscriber),
+            store,
+            "user.created",
+            "worker",
+            "host.1",
+            &cfg,
+        );
+        assert!(result.is_err());
+    }
+}
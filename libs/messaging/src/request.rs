@@ -0,0 +1,400 @@
+//! Scatter-gather and streaming request/reply.
+//!
+//! `Broker::request_many` publishes one request and collects the replies of
+//! every responder (use plain subscriptions, not a queue group, so each
+//! replica answers). `Broker::request_stream` reads a chunked reply sent by
+//! a `stream_handler` responder: every chunk is a reply on the request's
+//! inbox and a last, empty reply carries the `stream_end` header.
+
+use std::{fmt, sync::Arc, time::Duration};
+
+use async_nats::StatusCode;
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt, stream::BoxStream};
+use serde::{Serialize, de::DeserializeOwned};
+use tokio::time::Instant;
+
+use crate::{
+    Codec, Handler, Message, MessagingError,
+    nats::{
+        factory::{HEADER_STREAM_END, MessageFactory},
+        middleware::NatsHandlerFn,
+    },
+};
+
+/// Replies of a `Broker::request_stream`, ended by the responder's
+/// end-of-stream marker.
+pub type ReplyStream<R> = BoxStream<'static, Result<R, MessagingError>>;
+
+/// When `Broker::request_many` stops collecting replies.
+///
+/// Collection ends at `timeout` at the latest, or earlier once
+/// `max_replies` replies arrived or, with `sentinel`, at the first empty
+/// reply (which is not returned).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestMany {
+    pub timeout: Duration,
+    pub max_replies: Option<usize>,
+    pub sentinel: bool,
+}
+
+impl RequestMany {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            max_replies: None,
+            sentinel: false,
+        }
+    }
+
+    pub fn with_max_replies(mut self, max_replies: usize) -> Self {
+        self.max_replies = Some(max_replies);
+        self
+    }
+
+    /// Stop at the first reply with an empty payload.
+    pub fn with_sentinel(mut self) -> Self {
+        self.sentinel = true;
+        self
+    }
+}
+
+/// Responder that answers a request with a stream of chunks.
+///
+/// Each item is encoded with the request's codec and sent as one reply;
+/// the end of the stream is marked with an empty `stream_end` reply. An
+/// `Err` item ends the stream: it is returned to the subscription and sent
+/// to the requester as an error reply.
+///
+/// ```rust,ignore
+/// nats.subscribe("orders.export", stream_handler(|msg| {
+///     let query: ExportQuery = msg.decode()?;
+///     Ok(repo.stream_orders(query))
+/// }))
+/// .await?;
+/// ```
+pub fn stream_handler<F, S, R>(f: F) -> Handler
+where
+    F: Fn(Message) -> Result<S, MessagingError> + Send + Sync + 'static,
+    S: Stream<Item = Result<R, MessagingError>> + Send + 'static,
+    R: Serialize + Send + 'static,
+{
+    Arc::new(move |msg: Message| {
+        let replier = msg.replier.clone();
+        let encoding = msg.encoding();
+        let chunks = f(msg);
+        Box::pin(async move {
+            let replier = replier
+                .ok_or_else(|| MessagingError::service(400, "a streaming reply needs a request"))?;
+            let encoding = encoding?;
+            let mut chunks = std::pin::pin!(chunks?);
+            while let Some(chunk) = chunks.next().await {
+                replier.send(encoding.encode(&chunk?)?, false).await?;
+            }
+            replier.send(Bytes::new(), true).await?;
+            Ok(None)
+        })
+    })
+}
+
+/// Publishes replies to the request a `Message` arrived with.
+#[derive(Clone)]
+pub(crate) struct Replier {
+    pub(crate) subject: async_nats::Subject,
+    pub(crate) factory: Arc<MessageFactory>,
+    pub(crate) publish: NatsHandlerFn,
+    /// The request's `content-type`, used for the replies.
+    pub(crate) content_type: Option<String>,
+}
+
+impl fmt::Debug for Replier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Replier")
+            .field("subject", &self.subject)
+            .finish()
+    }
+}
+
+impl Replier {
+    async fn send(&self, data: Bytes, end: bool) -> Result<(), MessagingError> {
+        let mut reply = self.factory.build_reply(
+            self.subject.clone(),
+            Ok(data),
+            self.content_type.as_deref(),
+        )?;
+        if end {
+            reply
+                .headers
+                .get_or_insert_default()
+                .insert(HEADER_STREAM_END, "true");
+        }
+        (self.publish)(reply).await
+    }
+}
+
+fn is_no_responders(reply: &async_nats::Message) -> bool {
+    reply.status == Some(StatusCode::NO_RESPONDERS)
+}
+
+fn is_stream_end(reply: &async_nats::Message) -> bool {
+    reply
+        .headers
+        .as_ref()
+        .is_some_and(|h| h.get(HEADER_STREAM_END).is_some())
+}
+
+/// Collect the replies to a request sent to `subject` per `options`.
+pub(crate) async fn gather<R, S>(
+    factory: &MessageFactory,
+    subject: &str,
+    mut replies: S,
+    options: RequestMany,
+) -> Result<Vec<Result<R, MessagingError>>, MessagingError>
+where
+    R: DeserializeOwned,
+    S: Stream<Item = async_nats::Message> + Unpin,
+{
+    let deadline = Instant::now() + options.timeout;
+    let mut gathered = Vec::new();
+    while options.max_replies.is_none_or(|max| gathered.len() < max) {
+        let reply = match tokio::time::timeout_at(deadline, replies.next()).await {
+            Ok(Some(reply)) => reply,
+            Ok(None) | Err(_) => break,
+        };
+        if is_no_responders(&reply) {
+            return Err(MessagingError::NoResponders {
+                subject: subject.to_string(),
+            });
+        }
+        if options.sentinel && reply.payload.is_empty() {
+            break;
+        }
+        gathered.push(factory.decode_reply(&reply));
+    }
+    Ok(gathered)
+}
+
+/// Decode the chunks of a streamed reply to a request sent to `subject`.
+///
+/// Each chunk must arrive within `timeout` of the previous one. The stream
+/// ends after the end-of-stream marker, an error reply, a timeout or the
+/// inbox closing; the last three are yielded as an error first.
+pub(crate) fn reply_stream<R, S>(
+    factory: Arc<MessageFactory>,
+    subject: String,
+    replies: S,
+    timeout: Duration,
+) -> ReplyStream<R>
+where
+    R: DeserializeOwned + Send + 'static,
+    S: Stream<Item = async_nats::Message> + Send + Unpin + 'static,
+{
+    futures_util::stream::unfold(Some(replies), move |replies| {
+        let factory = Arc::clone(&factory);
+        let subject = subject.clone();
+        async move {
+            let mut replies = replies?;
+            let item = match tokio::time::timeout(timeout, replies.next()).await {
+                Err(_) => Err(MessagingError::Timeout(timeout)),
+                Ok(None) => Err(MessagingError::Closed),
+                Ok(Some(reply)) if is_no_responders(&reply) => {
+                    Err(MessagingError::NoResponders { subject })
+                }
+                Ok(Some(reply)) if is_stream_end(&reply) => return None,
+                Ok(Some(reply)) => match factory.reply_error(&reply) {
+                    Some(err) => Err(err),
+                    // A chunk that fails to decode does not end the stream.
+                    None => return Some((factory.decode_reply(&reply), Some(replies))),
+                },
+            };
+            Some((item, None))
+        }
+    })
+    .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use ro_config::config::nats::NatsConfig;
+
+    use super::*;
+    use crate::{Broker, Subscriber, memory::MemoryBroker, reply_handler};
+
+    const WAIT: Duration = Duration::from_secs(1);
+
+    fn broker() -> MemoryBroker {
+        MemoryBroker::new("test", NatsConfig::default(), Vec::new())
+    }
+
+    /// Responder that answers `reply` after `delay`; an empty `reply` is a
+    /// sentinel.
+    async fn respond(broker: &MemoryBroker, topic: &str, delay: Duration, reply: &'static [u8]) {
+        broker
+            .subscribe(
+                topic,
+                reply_handler(move |_msg: Message| async move {
+                    tokio::time::sleep(delay).await;
+                    Ok(Some(Bytes::from_static(reply)))
+                }),
+            )
+            .await
+            .unwrap();
+    }
+
+    fn numbers(replies: Vec<Result<i64, MessagingError>>) -> Vec<i64> {
+        let mut numbers: Vec<i64> = replies.into_iter().map(Result::unwrap).collect();
+        numbers.sort();
+        numbers
+    }
+
+    #[tokio::test]
+    async fn gather_collects_every_reply_until_timeout() {
+        let broker = broker();
+        respond(&broker, "census", Duration::ZERO, b"1").await;
+        respond(&broker, "census", Duration::ZERO, b"2").await;
+
+        let options = RequestMany::new(Duration::from_millis(100));
+        let replies = broker
+            .request_many::<_, i64>("census", &(), HashMap::new(), options)
+            .await
+            .unwrap();
+        assert_eq!(numbers(replies), [1, 2]);
+    }
+
+    #[tokio::test]
+    async fn gather_stops_at_max_replies() {
+        let broker = broker();
+        for reply in [b"1", b"2", b"3"] {
+            respond(&broker, "census", Duration::ZERO, reply).await;
+        }
+
+        let started = Instant::now();
+        let options = RequestMany::new(WAIT).with_max_replies(2);
+        let replies = broker
+            .request_many::<_, i64>("census", &(), HashMap::new(), options)
+            .await
+            .unwrap();
+        assert_eq!(replies.len(), 2);
+        assert!(started.elapsed() < WAIT, "did not stop at max_replies");
+    }
+
+    #[tokio::test]
+    async fn gather_stops_at_sentinel() {
+        let broker = broker();
+        respond(&broker, "census", Duration::ZERO, b"1").await;
+        respond(&broker, "census", Duration::from_millis(50), b"").await;
+        respond(&broker, "census", Duration::from_millis(500), b"3").await;
+
+        let started = Instant::now();
+        let options = RequestMany::new(WAIT).with_sentinel();
+        let replies = broker
+            .request_many::<_, i64>("census", &(), HashMap::new(), options)
+            .await
+            .unwrap();
+        assert_eq!(numbers(replies), [1]);
+        assert!(started.elapsed() < Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn gather_fails_without_responders() {
+        let err = broker()
+            .request_many::<_, i64>("census", &(), HashMap::new(), RequestMany::new(WAIT))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, MessagingError::NoResponders { ref subject } if subject == "census"),
+            "unexpected error: {err:?}"
+        );
+
+        // As reported by a NATS server: a 503 status reply.
+        let status = async_nats::Message {
+            subject: "_INBOX.1".into(),
+            reply: None,
+            payload: Bytes::new(),
+            headers: None,
+            status: Some(StatusCode::NO_RESPONDERS),
+            description: None,
+            length: 0,
+        };
+        let factory = MessageFactory::new("test".to_string(), Arc::new(NatsConfig::default()));
+        let replies = futures_util::stream::iter([status]);
+        let err = gather::<i64, _>(&factory, "census", replies, RequestMany::new(WAIT))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, MessagingError::NoResponders { .. }),
+            "unexpected error: {err:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn reply_stream_ends_at_stream_end_marker() {
+        let broker = broker();
+        broker
+            .subscribe(
+                "export",
+                stream_handler(|_msg| Ok(futures_util::stream::iter([Ok(1), Ok(2), Ok(3)]))),
+            )
+            .await
+            .unwrap();
+
+        let chunks: Vec<i64> = broker
+            .request_stream::<_, i64>("export", &(), HashMap::new(), WAIT)
+            .await
+            .unwrap()
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(chunks, [1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn reply_stream_ends_after_error_reply() {
+        let broker = broker();
+        broker
+            .subscribe(
+                "export",
+                stream_handler(|_msg| {
+                    Ok(futures_util::stream::iter([
+                        Ok(1),
+                        Err(MessagingError::service(422, "bad chunk")),
+                        Ok(3),
+                    ]))
+                }),
+            )
+            .await
+            .unwrap();
+
+        let chunks: Vec<Result<i64, MessagingError>> = broker
+            .request_stream("export", &(), HashMap::new(), WAIT)
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(chunks.len(), 2, "unexpected chunks: {chunks:?}");
+        assert_eq!(*chunks[0].as_ref().unwrap(), 1);
+        assert!(
+            matches!(chunks[1], Err(MessagingError::Service { code: 422, .. })),
+            "unexpected error: {:?}",
+            chunks[1]
+        );
+    }
+
+    #[tokio::test]
+    async fn reply_stream_times_out_between_chunks() {
+        let broker = broker();
+        respond(&broker, "export", WAIT, b"1").await;
+
+        let mut chunks = broker
+            .request_stream::<_, i64>("export", &(), HashMap::new(), Duration::from_millis(50))
+            .await
+            .unwrap();
+        assert!(matches!(
+            chunks.next().await,
+            Some(Err(MessagingError::Timeout(_)))
+        ));
+        assert!(chunks.next().await.is_none());
+    }
+}
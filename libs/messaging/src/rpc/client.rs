@@ -0,0 +1,53 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{Broker, MessagingError, rpc::Endpoint};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Typed caller for `Endpoint` definitions.
+///
+/// Error replies come back as `MessagingError::Service { code, description }`.
+#[derive(Debug)]
+pub struct RpcClient<B> {
+    broker: Arc<B>,
+    timeout: Duration,
+}
+
+impl<B> Clone for RpcClient<B> {
+    fn clone(&self) -> Self {
+        Self {
+            broker: Arc::clone(&self.broker),
+            timeout: self.timeout,
+        }
+    }
+}
+
+impl<B: Broker> RpcClient<B> {
+    pub fn new(broker: Arc<B>) -> Self {
+        Self {
+            broker,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub async fn call<Req, Res>(
+        &self,
+        endpoint: &Endpoint<Req, Res>,
+        request: &Req,
+    ) -> Result<Res, MessagingError>
+    where
+        Req: Serialize + Send + Sync,
+        Res: DeserializeOwned,
+    {
+        self.broker
+            .request(endpoint.subject(), request, HashMap::new(), self.timeout)
+            .await
+    }
+}
@@ -0,0 +1,41 @@
+use std::{fmt, marker::PhantomData};
+
+/// A typed RPC endpoint definition shared by server and client.
+///
+/// ```rust,ignore
+/// pub const GET_USER: Endpoint<GetUser, User> = Endpoint::new("user.get");
+///
+/// service.add(&GET_USER, |req: GetUser| async move { ... });
+/// let user = rpc.call(&GET_USER, &GetUser { id }).await?;
+/// ```
+pub struct Endpoint<Req, Res> {
+    subject: &'static str,
+    _types: PhantomData<fn(Req) -> Res>,
+}
+
+impl<Req, Res> Endpoint<Req, Res> {
+    pub const fn new(subject: &'static str) -> Self {
+        Self {
+            subject,
+            _types: PhantomData,
+        }
+    }
+
+    pub fn subject(&self) -> &'static str {
+        self.subject
+    }
+}
+
+impl<Req, Res> Clone for Endpoint<Req, Res> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Req, Res> Copy for Endpoint<Req, Res> {}
+
+impl<Req, Res> fmt::Debug for Endpoint<Req, Res> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Endpoint").field(&self.subject).finish()
+    }
+}
@@ -0,0 +1,113 @@
+//! Wire types for the NATS micro service protocol (`$SRV.PING|INFO|STATS`),
+//! so `nats micro ls` / `nats micro info` can discover our services.
+
+use std::{collections::HashMap, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+pub const PING_RESPONSE_TYPE: &str = "io.nats.micro.v1.ping_response";
+pub const INFO_RESPONSE_TYPE: &str = "io.nats.micro.v1.info_response";
+pub const STATS_RESPONSE_TYPE: &str = "io.nats.micro.v1.stats_response";
+
+/// Discovery verbs answered by every service instance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verb {
+    Ping,
+    Info,
+    Stats,
+}
+
+impl Verb {
+    pub const ALL: [Verb; 3] = [Verb::Ping, Verb::Info, Verb::Stats];
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Verb::Ping => "PING",
+            Verb::Info => "INFO",
+            Verb::Stats => "STATS",
+        }
+    }
+
+    /// `$SRV.<VERB>`, `$SRV.<VERB>.<name>` and `$SRV.<VERB>.<name>.<id>`.
+    pub fn subjects(&self, name: &str, id: &str) -> [String; 3] {
+        let verb = self.as_str();
+        [
+            format!("$SRV.{verb}"),
+            format!("$SRV.{verb}.{name}"),
+            format!("$SRV.{verb}.{name}.{id}"),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PingResponse {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub name: String,
+    pub id: String,
+    pub version: String,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EndpointInfo {
+    pub name: String,
+    pub subject: String,
+    pub queue_group: String,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InfoResponse {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub name: String,
+    pub id: String,
+    pub version: String,
+    pub description: String,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    pub endpoints: Vec<EndpointInfo>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EndpointStats {
+    pub name: String,
+    pub subject: String,
+    pub queue_group: String,
+    pub num_requests: u64,
+    pub num_errors: u64,
+    /// Total processing time (nanoseconds).
+    pub processing_time: u64,
+    /// Average processing time (nanoseconds).
+    pub average_processing_time: u64,
+    pub last_error: String,
+}
+
+impl EndpointStats {
+    pub fn record(&mut self, elapsed: Duration, error: Option<String>) {
+        self.num_requests += 1;
+        self.processing_time += elapsed.as_nanos() as u64;
+        self.average_processing_time = self.processing_time / self.num_requests;
+        if let Some(err) = error {
+            self.num_errors += 1;
+            self.last_error = err;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsResponse {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub name: String,
+    pub id: String,
+    pub version: String,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    /// RFC3339 start time of this instance.
+    pub started: String,
+    pub endpoints: Vec<EndpointStats>,
+}
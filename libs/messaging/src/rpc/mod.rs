@@ -0,0 +1,8 @@
+pub mod client;
+pub mod endpoint;
+pub mod micro;
+pub mod service;
+
+pub use client::RpcClient;
+pub use endpoint::Endpoint;
+pub use service::{Service, ServiceBuilder};
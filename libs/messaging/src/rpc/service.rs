@@ -0,0 +1,376 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use chrono::{SecondsFormat, Utc};
+use serde::{Serialize, de::DeserializeOwned};
+
+use ro_common::id::generate_nanoid;
+
+use crate::{
+    Codec, Message, MessagingError, QueueSubscriber, Subscriber, SubscriptionId, reply_handler,
+    rpc::{
+        Endpoint,
+        micro::{
+            EndpointInfo, EndpointStats, INFO_RESPONSE_TYPE, InfoResponse, PING_RESPONSE_TYPE,
+            PingResponse, STATS_RESPONSE_TYPE, StatsResponse, Verb,
+        },
+    },
+};
+
+/// Queue group shared by all instances of a service (NATS micro default).
+const DEFAULT_QUEUE_GROUP: &str = "q";
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+type CallFuture = Pin<Box<dyn Future<Output = Result<Bytes, MessagingError>> + Send>>;
+
+/// Type-erased endpoint: request message → encoded reply payload.
+type CallFn = Arc<dyn Fn(Message) -> CallFuture + Send + Sync>;
+
+struct Registration {
+    subject: String,
+    timeout: Option<Duration>,
+    call: CallFn,
+}
+
+/// Builder for a typed RPC service.
+///
+/// ```rust,ignore
+/// let service = ServiceBuilder::new("users", "1.0.0")
+///     .description("user lookups")
+///     .add(&GET_USER, |req: GetUser| async move { ... })
+///     .endpoint_with_timeout("user.search", Duration::from_secs(2), search)
+///     .start(Arc::new(nats))
+///     .await?;
+/// ```
+pub struct ServiceBuilder {
+    name: String,
+    version: String,
+    description: String,
+    queue_group: String,
+    timeout: Duration,
+    metadata: HashMap<String, String>,
+    endpoints: Vec<Registration>,
+}
+
+impl ServiceBuilder {
+    pub fn new(name: impl Into<String>, version: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            version: version.into(),
+            description: String::new(),
+            queue_group: DEFAULT_QUEUE_GROUP.to_string(),
+            timeout: DEFAULT_TIMEOUT,
+            metadata: HashMap::new(),
+            endpoints: Vec::new(),
+        }
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    /// Queue group used to load-balance requests across instances.
+    pub fn queue_group(mut self, group: impl Into<String>) -> Self {
+        self.queue_group = group.into();
+        self
+    }
+
+    /// Default handler timeout for endpoints without their own.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// Register a handler for a typed `Endpoint` definition.
+    pub fn add<Req, Res, F, Fut>(self, endpoint: &Endpoint<Req, Res>, f: F) -> Self
+    where
+        Req: DeserializeOwned + Send + 'static,
+        Res: Serialize + Send + 'static,
+        F: Fn(Req) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Res, MessagingError>> + Send + 'static,
+    {
+        self.register(endpoint.subject(), None, f)
+    }
+
+    /// Register a handler on `subject`.
+    ///
+    /// `Req` is decoded with the codec named by the request's `content-type`
+    /// and `Res` is encoded with the same codec.
+    ///
+    /// Return `MessagingError::service(code, description)` for a structured
+    /// error reply; any other error replies with code 500.
+    pub fn endpoint<Req, Res, F, Fut>(self, subject: &str, f: F) -> Self
+    where
+        Req: DeserializeOwned + Send + 'static,
+        Res: Serialize + Send + 'static,
+        F: Fn(Req) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Res, MessagingError>> + Send + 'static,
+    {
+        self.register(subject, None, f)
+    }
+
+    /// Like `endpoint`, overriding the service-wide timeout.
+    pub fn endpoint_with_timeout<Req, Res, F, Fut>(
+        self,
+        subject: &str,
+        timeout: Duration,
+        f: F,
+    ) -> Self
+    where
+        Req: DeserializeOwned + Send + 'static,
+        Res: Serialize + Send + 'static,
+        F: Fn(Req) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Res, MessagingError>> + Send + 'static,
+    {
+        self.register(subject, Some(timeout), f)
+    }
+
+    fn register<Req, Res, F, Fut>(mut self, subject: &str, timeout: Option<Duration>, f: F) -> Self
+    where
+        Req: DeserializeOwned + Send + 'static,
+        Res: Serialize + Send + 'static,
+        F: Fn(Req) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Res, MessagingError>> + Send + 'static,
+    {
+        let f = Arc::new(f);
+        let call: CallFn = Arc::new(move |msg: Message| {
+            let f = Arc::clone(&f);
+            Box::pin(async move {
+                let encoding = msg.encoding()?;
+                let req: Req = encoding.decode(&msg.data)?;
+                let res = f(req).await?;
+                encoding.encode(&res)
+            })
+        });
+
+        self.endpoints.push(Registration {
+            subject: subject.to_string(),
+            timeout,
+            call,
+        });
+        self
+    }
+
+    /// Subscribe every endpoint (queue group) and the `$SRV` discovery subjects.
+    ///
+    /// If a subscription fails, the ones already made are cancelled, so a
+    /// service that failed to start does not answer requests.
+    pub async fn start<S>(self, client: Arc<S>) -> Result<Service<S>, MessagingError>
+    where
+        S: Subscriber + QueueSubscriber + 'static,
+    {
+        let state = Arc::new(ServiceState {
+            id: generate_nanoid(),
+            name: self.name,
+            version: self.version,
+            description: self.description,
+            metadata: self.metadata,
+            started: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            stats: Mutex::new(
+                self.endpoints
+                    .iter()
+                    .map(|r| EndpointStats {
+                        name: r.subject.clone(),
+                        subject: r.subject.clone(),
+                        queue_group: self.queue_group.clone(),
+                        ..Default::default()
+                    })
+                    .collect(),
+            ),
+        });
+
+        let mut service = Service {
+            client,
+            state,
+            subscriptions: Vec::new(),
+        };
+
+        for (idx, registration) in self.endpoints.into_iter().enumerate() {
+            let timeout = registration.timeout.unwrap_or(self.timeout);
+            let call = registration.call;
+            let state = Arc::clone(&service.state);
+
+            let handler = reply_handler(move |msg: Message| {
+                let call = Arc::clone(&call);
+                let state = Arc::clone(&state);
+                async move {
+                    let started = Instant::now();
+                    let result = tokio::time::timeout(timeout, call(msg))
+                        .await
+                        .unwrap_or_else(|_| Err(MessagingError::Timeout(timeout)));
+                    state.record(idx, started.elapsed(), result.as_ref().err());
+                    result.map(Some)
+                }
+            });
+
+            match service
+                .client
+                .queue_subscribe(&registration.subject, &self.queue_group, handler)
+                .await
+            {
+                Ok(id) => service.subscriptions.push(id),
+                Err(e) => return Err(service.abandon(e).await),
+            }
+        }
+
+        for verb in Verb::ALL {
+            for subject in verb.subjects(&service.state.name, &service.state.id) {
+                let state = Arc::clone(&service.state);
+                let handler = reply_handler(move |_msg: Message| {
+                    let state = Arc::clone(&state);
+                    async move { state.discovery(verb).map(Some) }
+                });
+                match Subscriber::subscribe(service.client.as_ref(), &subject, handler).await {
+                    Ok(id) => service.subscriptions.push(id),
+                    Err(e) => return Err(service.abandon(e).await),
+                }
+            }
+        }
+
+        tracing::info!(
+            service = %service.state.name,
+            id = %service.state.id,
+            "RPC: service started"
+        );
+
+        Ok(service)
+    }
+}
+
+struct ServiceState {
+    id: String,
+    name: String,
+    version: String,
+    description: String,
+    metadata: HashMap<String, String>,
+    started: String,
+    stats: Mutex<Vec<EndpointStats>>,
+}
+
+impl ServiceState {
+    fn record(&self, idx: usize, elapsed: Duration, error: Option<&MessagingError>) {
+        if let Ok(mut stats) = self.stats.lock() {
+            stats[idx].record(elapsed, error.map(|e| e.to_string()));
+        }
+    }
+
+    fn stats(&self) -> Vec<EndpointStats> {
+        self.stats.lock().map(|s| s.clone()).unwrap_or_default()
+    }
+
+    fn info(&self) -> InfoResponse {
+        InfoResponse {
+            kind: INFO_RESPONSE_TYPE.to_string(),
+            name: self.name.clone(),
+            id: self.id.clone(),
+            version: self.version.clone(),
+            description: self.description.clone(),
+            metadata: self.metadata.clone(),
+            endpoints: self
+                .stats()
+                .into_iter()
+                .map(|s| EndpointInfo {
+                    name: s.name,
+                    subject: s.subject,
+                    queue_group: s.queue_group,
+                    metadata: HashMap::new(),
+                })
+                .collect(),
+        }
+    }
+
+    fn discovery(&self, verb: Verb) -> Result<Bytes, MessagingError> {
+        let body = match verb {
+            Verb::Ping => serde_json::to_vec(&PingResponse {
+                kind: PING_RESPONSE_TYPE.to_string(),
+                name: self.name.clone(),
+                id: self.id.clone(),
+                version: self.version.clone(),
+                metadata: self.metadata.clone(),
+            }),
+            Verb::Info => serde_json::to_vec(&self.info()),
+            Verb::Stats => serde_json::to_vec(&StatsResponse {
+                kind: STATS_RESPONSE_TYPE.to_string(),
+                name: self.name.clone(),
+                id: self.id.clone(),
+                version: self.version.clone(),
+                metadata: self.metadata.clone(),
+                started: self.started.clone(),
+                endpoints: self.stats(),
+            }),
+        };
+        body.map(Bytes::from).map_err(MessagingError::encode)
+    }
+}
+
+/// A running RPC service instance.
+pub struct Service<S> {
+    client: Arc<S>,
+    state: Arc<ServiceState>,
+    /// By id: services sharing a connection share the `$SRV.<VERB>`
+    /// subjects.
+    subscriptions: Vec<SubscriptionId>,
+}
+
+impl<S> Service<S>
+where
+    S: Subscriber + QueueSubscriber + 'static,
+{
+    /// Unique id of this instance (used in `$SRV.<VERB>.<name>.<id>`).
+    pub fn id(&self) -> &str {
+        &self.state.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.state.name
+    }
+
+    pub fn info(&self) -> InfoResponse {
+        self.state.info()
+    }
+
+    pub fn stats(&self) -> Vec<EndpointStats> {
+        self.state.stats()
+    }
+
+    /// Unsubscribe every endpoint and discovery subject.
+    pub async fn stop(self) -> Result<(), MessagingError> {
+        for id in &self.subscriptions {
+            self.client.unsubscribe_id(*id).await?;
+        }
+        tracing::info!(service = %self.state.name, id = %self.state.id, "RPC: service stopped");
+        Ok(())
+    }
+
+    /// Undo a partial `ServiceBuilder::start`; returns `err` for the caller.
+    async fn abandon(self, err: MessagingError) -> MessagingError {
+        for id in &self.subscriptions {
+            if let Err(e) = self.client.unsubscribe_id(*id).await {
+                tracing::warn!(service = %self.state.name, error = %e, "RPC: unsubscribe failed");
+            }
+        }
+        err
+    }
+}
+
+impl<S> std::fmt::Debug for Service<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Service")
+            .field("name", &self.state.name)
+            .field("id", &self.state.id)
+            .field("version", &self.state.version)
+            .finish()
+    }
+}
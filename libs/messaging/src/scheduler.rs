@@ -0,0 +1,48 @@
+use std::{collections::HashMap, time::Duration};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::{MessagingError, Publisher};
+
+/// Publish messages at a later time.
+///
+/// A scheduled message is stored by the implementation and published by a
+/// dispatcher once due, at the earliest at `at`. The returned id is also
+/// the message id (`Nats-Msg-Id`), and cancels the message until it has
+/// been published.
+#[async_trait]
+pub trait ScheduledPublisher: Publisher {
+    /// Schedule `data` for `at`; a time in the past publishes on the next
+    /// dispatch. Returns the schedule id.
+    async fn publish_at(
+        &self,
+        topic: &str,
+        data: Bytes,
+        attrs: HashMap<String, String>,
+        at: DateTime<Utc>,
+    ) -> Result<String, MessagingError>;
+
+    /// Schedule `data` for `delay` from now. A delay past the largest
+    /// representable time is rejected.
+    async fn publish_after(
+        &self,
+        topic: &str,
+        data: Bytes,
+        attrs: HashMap<String, String>,
+        delay: Duration,
+    ) -> Result<String, MessagingError> {
+        let at = TimeDelta::from_std(delay)
+            .ok()
+            .and_then(|delay| Utc::now().checked_add_signed(delay))
+            .ok_or_else(|| {
+                MessagingError::service(400, format!("delay out of range: {delay:?}"))
+            })?;
+        self.publish_at(topic, data, attrs, at).await
+    }
+
+    /// Cancel a scheduled message. Returns `false` if it is unknown or was
+    /// already published.
+    async fn cancel(&self, id: &str) -> Result<bool, MessagingError>;
+}
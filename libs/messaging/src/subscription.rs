@@ -0,0 +1,422 @@
+use std::{
+    any::Any,
+    future::Future,
+    panic::AssertUnwindSafe,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use futures_util::{FutureExt, StreamExt};
+use opentelemetry::{KeyValue, metrics::UpDownCounter};
+use ro_config::config::nats::NatsConfig;
+use ro_telemetry::meter::messaging::{
+    ACTIVE_HANDLERS, PANIC_COUNT, PENDING_MESSAGES, SLOW_CONSUMER_COUNT,
+};
+use tokio::{
+    sync::{Notify, OwnedSemaphorePermit, Semaphore},
+    task::{JoinHandle, JoinSet},
+};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::{
+    Handler, MessagingError,
+    ack::AckHandle,
+    codec::HEADER_CONTENT_TYPE,
+    nats::{factory::MessageFactory, middleware::NatsHandlerFn},
+    request::Replier,
+};
+
+/// A single inbound message plus its acknowledgement handle.
+pub(crate) struct Delivery {
+    pub(crate) msg: async_nats::Message,
+    pub(crate) ack: AckHandle,
+}
+
+impl From<async_nats::Message> for Delivery {
+    fn from(msg: async_nats::Message) -> Self {
+        Self {
+            msg,
+            ack: AckHandle::default(),
+        }
+    }
+}
+
+/// Wrap a domain `Handler` into a transport-level `NatsHandlerFn`
+/// (factory reads the message and converts it, then calls the domain handler).
+///
+/// When the message is a request (has a reply subject) the handler's
+/// `Some(bytes)` is published back as the reply, and a handler error is
+/// published as an error reply so the requester fails fast instead of
+/// timing out. Replies carry the request's `content-type`.
+///
+/// The handler runs inside the publisher's `RequestContext`. A panic in
+/// the handler becomes a `MessagingError::Handler`, so it is replied,
+/// retried and dead-lettered like any other failure.
+pub(crate) fn wrap_handler(
+    factory: Arc<MessageFactory>,
+    handler: Handler,
+    publish_reply: NatsHandlerFn,
+) -> NatsHandlerFn {
+    Arc::new(move |nats_msg: async_nats::Message| {
+        let handler = Arc::clone(&handler);
+        let factory = Arc::clone(&factory);
+        let publish_reply = Arc::clone(&publish_reply);
+        Box::pin(async move {
+            let reply_to = nats_msg.reply.clone();
+            let mut msg = factory.read_message(nats_msg)?;
+            let content_type = msg.attr(HEADER_CONTENT_TYPE).map(str::to_string);
+            msg.replier = reply_to.clone().map(|subject| Replier {
+                subject,
+                factory: Arc::clone(&factory),
+                publish: Arc::clone(&publish_reply),
+                content_type: content_type.clone(),
+            });
+            let topic = msg.topic.clone();
+            let ctx = msg.context();
+            let result = catch_panic(&topic, ctx.scope(async move { handler(msg).await })).await;
+
+            if let Some(reply_to) = reply_to {
+                let content_type = content_type.as_deref();
+                let reply = match &result {
+                    Ok(Some(data)) => {
+                        Some(factory.build_reply(reply_to, Ok(data.clone()), content_type)?)
+                    }
+                    Ok(None) => None,
+                    Err(e) => Some(factory.build_reply(reply_to, Err(e), content_type)?),
+                };
+                if let Some(reply) = reply {
+                    publish_reply(reply).await?;
+                }
+            }
+
+            result.map(|_| ()).map_err(|e| match e {
+                e @ (MessagingError::Service { .. }
+                | MessagingError::Timeout(_)
+                | MessagingError::Codec { .. }
+                | MessagingError::Handler { .. }) => e,
+                e => MessagingError::handler(e),
+            })
+        })
+    })
+}
+
+/// Per-subscription flow control.
+///
+/// Defaults come from `NatsConfig` (`max_concurrency`,
+/// `slow_consumer_threshold_ms`, `handler_timeout_secs`); override them for the subscriptions made
+/// through one handle with `NatsClient::with_subscribe_options`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubscribeOptions {
+    /// Handlers running at once (0 = unlimited).
+    pub max_concurrency: usize,
+    /// Handle one message at a time, in delivery order.
+    pub ordered: bool,
+    /// Report a slow consumer when a message waits this long for a slot.
+    pub slow_consumer_threshold: Duration,
+    /// Cancel a handler (including its middleware chain) that runs longer
+    /// than this; the message is then nak'd and the span opened by
+    /// `tracing_middleware` is marked `messaging.cancelled`.
+    pub handler_timeout: Option<Duration>,
+}
+
+impl SubscribeOptions {
+    pub fn from_config(cfg: &NatsConfig) -> Self {
+        Self {
+            max_concurrency: cfg.max_concurrency,
+            ordered: false,
+            slow_consumer_threshold: cfg.slow_consumer_threshold(),
+            handler_timeout: cfg.handler_timeout(),
+        }
+    }
+
+    pub fn with_max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = max_concurrency;
+        self
+    }
+
+    /// Strictly ordered, one-at-a-time processing.
+    pub fn ordered(mut self) -> Self {
+        self.ordered = true;
+        self
+    }
+
+    pub fn with_slow_consumer_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_consumer_threshold = threshold;
+        self
+    }
+
+    pub fn with_handler_timeout(mut self, timeout: Duration) -> Self {
+        self.handler_timeout = Some(timeout);
+        self
+    }
+}
+
+impl Default for SubscribeOptions {
+    fn default() -> Self {
+        Self::from_config(&NatsConfig::default())
+    }
+}
+
+/// Identifies one subscription, so it can be cancelled without touching
+/// other subscriptions to the same subject (see `Subscriber::unsubscribe_id`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+impl SubscriptionId {
+    pub(crate) fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// A running subscription: the drain loop and the handlers it spawned.
+#[derive(Debug)]
+pub(crate) struct Subscription {
+    stop: Arc<Notify>,
+    task: JoinHandle<()>,
+}
+
+impl Subscription {
+    /// Stop reading new messages and wait up to `deadline` for running
+    /// handlers; whatever is still running then is aborted.
+    ///
+    /// Dropping the stream unsubscribes at the server (core NATS) or stops
+    /// pulling (JetStream), so undelivered messages stay with the server.
+    /// Returns `false` if handlers had to be aborted.
+    pub(crate) async fn drain(self, topic: &str, deadline: Duration) -> bool {
+        self.stop.notify_one();
+        let abort = self.task.abort_handle();
+        match tokio::time::timeout(deadline, self.task).await {
+            Ok(_) => true,
+            Err(_) => {
+                abort.abort();
+                tracing::warn!(
+                    topic,
+                    deadline_ms = deadline.as_millis() as u64,
+                    "NATS: drain deadline exceeded, aborting handlers"
+                );
+                false
+            }
+        }
+    }
+}
+
+/// Spawn a drain loop for a subscription stream.
+///
+/// One Tokio task per subscription (same as Go goroutine per Subscribe).
+/// Each handler runs with its delivery's `AckHandle` in scope; anything
+/// the handler did not settle itself is acked on `Ok` and nak'd on `Err`.
+///
+/// At most `options.max_concurrency` handlers run at once; while all slots
+/// are busy the loop stops reading `stream`, so backpressure reaches the
+/// server. In `ordered` mode each handler finishes before the next message
+/// is read. The loop ends when `stream` ends or on `Subscription::drain`,
+/// after the handlers it started have finished.
+pub(crate) fn spawn_drain<S>(
+    topic: String,
+    mut stream: S,
+    handler: NatsHandlerFn,
+    options: SubscribeOptions,
+) -> Subscription
+where
+    S: futures_util::Stream<Item = Delivery> + Send + Unpin + 'static,
+{
+    let limit = match (options.ordered, options.max_concurrency) {
+        (true, _) => None,
+        (false, 0) => None,
+        (false, n) => Some(Arc::new(Semaphore::new(n))),
+    };
+    let stop = Arc::new(Notify::new());
+    let stopped = Arc::clone(&stop);
+
+    let task = tokio::spawn(async move {
+        tracing::debug!(topic, ?options, "NATS: subscription started");
+        let labels = [KeyValue::new("subject", topic.clone())];
+        let mut running = JoinSet::new();
+        loop {
+            let delivery = tokio::select! {
+                biased;
+                _ = stopped.notified() => break,
+                next = stream.next() => match next {
+                    Some(delivery) => delivery,
+                    None => break,
+                },
+            };
+            while running.try_join_next().is_some() {}
+            let pending = InFlight::new(&PENDING_MESSAGES, &topic);
+
+            if options.ordered {
+                handle_delivery(&topic, &handler, delivery, options.handler_timeout).await;
+                drop(pending);
+                continue;
+            }
+
+            let permit = match &limit {
+                Some(sem) => {
+                    let Some(permit) =
+                        acquire_slot(sem, &topic, options.slow_consumer_threshold, &labels).await
+                    else {
+                        break;
+                    };
+                    Some(permit)
+                }
+                None => None,
+            };
+
+            let h = Arc::clone(&handler);
+            let t = topic.clone();
+            running.spawn(async move {
+                let _permit = permit;
+                let _pending = pending;
+                handle_delivery(&t, &h, delivery, options.handler_timeout).await;
+            });
+        }
+
+        drop(stream);
+        if !running.is_empty() {
+            tracing::debug!(topic, running = running.len(), "NATS: waiting for handlers");
+        }
+        while running.join_next().await.is_some() {}
+        tracing::debug!(topic, "NATS: subscription ended");
+    });
+
+    Subscription { stop, task }
+}
+
+/// Wait for a free handler slot, reporting a slow consumer if that takes
+/// longer than `threshold`. `None` if the semaphore was closed.
+async fn acquire_slot(
+    sem: &Arc<Semaphore>,
+    topic: &str,
+    threshold: Duration,
+    labels: &[KeyValue],
+) -> Option<OwnedSemaphorePermit> {
+    if let Ok(permit) = Arc::clone(sem).try_acquire_owned() {
+        return Some(permit);
+    }
+
+    let started = Instant::now();
+    let acquire = Arc::clone(sem).acquire_owned();
+    tokio::pin!(acquire);
+    if let Ok(permit) = tokio::time::timeout(threshold, &mut acquire).await {
+        return permit.ok();
+    }
+
+    SLOW_CONSUMER_COUNT.add(1, labels);
+    tracing::warn!(
+        topic,
+        threshold_ms = threshold.as_millis() as u64,
+        "NATS: slow consumer, all handler slots busy"
+    );
+    let permit = acquire.await.ok();
+    tracing::debug!(
+        topic,
+        waited_ms = started.elapsed().as_millis() as u64,
+        "NATS: handler slot freed"
+    );
+    permit
+}
+
+async fn handle_delivery(
+    topic: &str,
+    handler: &NatsHandlerFn,
+    delivery: Delivery,
+    timeout: Option<Duration>,
+) {
+    let active = InFlight::new(&ACTIVE_HANDLERS, topic);
+    let ack = delivery.ack;
+    // Handler panics are caught in `wrap_handler`; this also covers the
+    // middleware chain so the message is still settled
+    let msg = delivery.msg;
+    let run = catch_panic(topic, ack.clone().scope(async move { handler(msg).await }));
+    let result = match timeout {
+        Some(timeout) => with_timeout(topic, timeout, run).await,
+        None => run.await,
+    };
+    drop(active);
+    if let Err(e) = &result {
+        tracing::error!(topic, error = %e, "NATS: handler error");
+    }
+    ack.settle(&result).await;
+}
+
+/// Counts one in `gauge` until dropped, so handlers aborted by a drain
+/// deadline are uncounted as well.
+struct InFlight {
+    gauge: &'static UpDownCounter<i64>,
+    labels: [KeyValue; 1],
+}
+
+impl InFlight {
+    fn new(gauge: &'static UpDownCounter<i64>, topic: &str) -> Self {
+        let labels = [KeyValue::new("subject", topic.to_string())];
+        gauge.add(1, &labels);
+        Self { gauge, labels }
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.gauge.add(-1, &self.labels);
+    }
+}
+
+/// Run `fut`, turning a panic into `MessagingError::Handler`.
+///
+/// The panic is logged (as an event on the current span) and counted in
+/// `PANIC_COUNT`.
+async fn catch_panic<T>(
+    topic: &str,
+    fut: impl Future<Output = Result<T, MessagingError>>,
+) -> Result<T, MessagingError> {
+    match AssertUnwindSafe(fut).catch_unwind().await {
+        Ok(result) => result,
+        Err(payload) => {
+            let reason = panic_message(payload.as_ref());
+            tracing::error!(topic, panic = %reason, "NATS: handler panicked");
+            PANIC_COUNT.add(1, &[KeyValue::new("subject", topic.to_string())]);
+            Err(MessagingError::handler(format!(
+                "handler panicked: {reason}"
+            )))
+        }
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic payload".to_string()
+    }
+}
+
+/// Run `fut` for at most `timeout`; past it the future is dropped
+/// (cancelled at its next `.await`) and `MessagingError::Timeout` returned.
+///
+/// The current span is marked `messaging.timed_out` and set to error; a
+/// span opened inside `fut` is marked by `tracing_middleware` instead.
+pub(crate) async fn with_timeout<T>(
+    topic: &str,
+    timeout: Duration,
+    fut: impl Future<Output = Result<T, MessagingError>>,
+) -> Result<T, MessagingError> {
+    match tokio::time::timeout(timeout, fut).await {
+        Ok(result) => result,
+        Err(_) => {
+            let span = tracing::Span::current();
+            span.set_attribute("messaging.timed_out", true);
+            span.set_status(opentelemetry::trace::Status::error("handler timed out"));
+            tracing::warn!(
+                topic,
+                timeout_ms = timeout.as_millis() as u64,
+                "NATS: handler timed out, cancelled"
+            );
+            Err(MessagingError::Timeout(timeout))
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use std::{fmt, marker::PhantomData, sync::Arc};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{Handler, Message, MessagingError, codec::Encoding};
+
+/// A topic name bound to the type of its payload.
+///
+/// Declare topics once as constants and use them on both sides, so a
+/// publisher and a subscriber cannot disagree on the name or the type:
+///
+/// ```rust,ignore
+/// pub const USER_CREATED: Topic<UserCreated> = Topic::new("user.created");
+///
+/// client.publish_to(&USER_CREATED, &event).await?;
+/// client.subscribe_to(&USER_CREATED, |evt: UserCreated| async move { ... }).await?;
+/// ```
+///
+/// Payloads are encoded with the publisher's codec and decoded with the
+/// codec named by the message's `content-type`, like `Message::decode`.
+pub struct Topic<T> {
+    name: &'static str,
+    _payload: PhantomData<fn() -> T>,
+}
+
+impl<T> Topic<T> {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            _payload: PhantomData,
+        }
+    }
+
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl<T: Serialize> Topic<T> {
+    /// Build an unsent message, e.g. for the outbox.
+    pub fn message(&self, payload: &T, encoding: Encoding) -> Result<Message, MessagingError> {
+        Message::encode(self.name, payload, encoding)
+    }
+}
+
+impl<T: DeserializeOwned + Send + 'static> Topic<T> {
+    /// Wrap `f` into a `Handler` that decodes each message as `T`.
+    ///
+    /// A payload that does not decode fails with a (non-retryable) codec
+    /// error before `f` runs.
+    pub fn handler<F, Fut>(&self, f: F) -> Handler
+    where
+        F: Fn(T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), MessagingError>> + Send + 'static,
+    {
+        let f = Arc::new(f);
+        Arc::new(move |msg: Message| {
+            let f = Arc::clone(&f);
+            Box::pin(async move {
+                let payload = msg.decode::<T>()?;
+                f(payload).await.map(|_| None)
+            })
+        })
+    }
+}
+
+// Manual impls: `T` is only a marker, so no bounds on it are needed.
+impl<T> Clone for Topic<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Topic<T> {}
+
+impl<T> fmt::Debug for Topic<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Topic").field(&self.name).finish()
+    }
+}
+
+impl<T> fmt::Display for Topic<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)
+    }
+}
@@ -5,7 +5,13 @@ use async_trait::async_trait;
 use bytes::Bytes;
 use std::time::Duration;
 
-use crate::{Message, MessagingError};
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{
+    CloudEvent, CloudEventMode, Message, MessagingError, SubscriptionId, Topic,
+    codec::{Codec, Encoding, HEADER_CONTENT_TYPE},
+    request::{ReplyStream, RequestMany},
+};
 
 pub type HandlerFuture =
     Pin<Box<dyn Future<Output = Result<Option<Bytes>, MessagingError>> + Send>>;
@@ -42,6 +48,11 @@ pub trait Publisher: Send + Sync + Debug {
     ) -> Result<(), MessagingError>;
 
     async fn close(&self) -> Result<(), MessagingError>;
+
+    /// Default codec for `publish_encoded` and requests.
+    fn encoding(&self) -> Encoding {
+        Encoding::Json
+    }
 }
 
 #[async_trait]
@@ -51,21 +62,89 @@ pub trait PublisherExt: Publisher {
         topic: &str,
         payload: &T,
     ) -> Result<(), MessagingError> {
-        let data = serde_json::to_vec(payload)
-            .map_err(|e| MessagingError::Serialization(e.to_string()))?;
-        self.publish(topic, Bytes::from(data), HashMap::new()).await
+        self.publish_with(topic, payload, Encoding::Json).await
+    }
+
+    /// Publish `payload` with the publisher's default codec.
+    async fn publish_encoded<T: serde::Serialize + Send + Sync>(
+        &self,
+        topic: &str,
+        payload: &T,
+    ) -> Result<(), MessagingError> {
+        self.publish_with(topic, payload, self.encoding()).await
+    }
+
+    /// Publish `payload` encoded with `encoding`, stamping its `content-type`.
+    async fn publish_with<T: serde::Serialize + Send + Sync>(
+        &self,
+        topic: &str,
+        payload: &T,
+        encoding: Encoding,
+    ) -> Result<(), MessagingError> {
+        let data = encoding.encode(payload)?;
+        let attrs = HashMap::from([(
+            HEADER_CONTENT_TYPE.to_string(),
+            encoding.content_type().to_string(),
+        )]);
+        self.publish(topic, data, attrs).await
+    }
+
+    /// Publish a CloudEvent in binary or structured mode.
+    async fn publish_event(
+        &self,
+        topic: &str,
+        event: CloudEvent,
+        mode: CloudEventMode,
+    ) -> Result<(), MessagingError> {
+        let msg = event.into_message(topic, mode)?;
+        self.publish(topic, msg.data, msg.attrs).await
+    }
+
+    /// Publish `payload` on a typed topic with the publisher's default codec.
+    async fn publish_to<T: Serialize + Send + Sync>(
+        &self,
+        topic: &Topic<T>,
+        payload: &T,
+    ) -> Result<(), MessagingError> {
+        self.publish_encoded(topic.name(), payload).await
     }
 }
 
 /// Fan-out pub/sub subscriber.
 #[async_trait]
 pub trait Subscriber: Send + Sync {
-    async fn subscribe(&self, topic: &str, handler: Handler) -> Result<(), MessagingError>;
+    async fn subscribe(
+        &self,
+        topic: &str,
+        handler: Handler,
+    ) -> Result<SubscriptionId, MessagingError>;
 
+    /// Cancel every subscription to `topic` made on this connection.
     async fn unsubscribe(&self, topic: &str) -> Result<(), MessagingError>;
+
+    /// Cancel one subscription, whichever kind; unknown ids are ignored.
+    async fn unsubscribe_id(&self, id: SubscriptionId) -> Result<(), MessagingError>;
+
     async fn close(&self) -> Result<(), MessagingError>;
 }
 
+/// Typed subscriptions; see `Topic`.
+#[async_trait]
+pub trait SubscriberExt: Subscriber {
+    async fn subscribe_to<T, F, Fut>(
+        &self,
+        topic: &Topic<T>,
+        f: F,
+    ) -> Result<SubscriptionId, MessagingError>
+    where
+        T: DeserializeOwned + Send + 'static,
+        F: Fn(T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), MessagingError>> + Send + 'static,
+    {
+        self.subscribe(topic.name(), topic.handler(f)).await
+    }
+}
+
 /// Competing-consumer (work-queue) subscriber.
 ///
 /// Only one subscriber in the same `group` will receive each message.
@@ -76,17 +155,76 @@ pub trait QueueSubscriber: Send + Sync {
         topic: &str,
         group: &str,
         handler: Handler,
-    ) -> Result<(), MessagingError>;
+    ) -> Result<SubscriptionId, MessagingError>;
+
+    async fn unsubscribe(&self, topic: &str) -> Result<(), MessagingError>;
+
+    async fn close(&self) -> Result<(), MessagingError>;
+}
+
+#[async_trait]
+pub trait QueueSubscriberExt: QueueSubscriber {
+    async fn queue_subscribe_to<T, F, Fut>(
+        &self,
+        topic: &Topic<T>,
+        group: &str,
+        f: F,
+    ) -> Result<SubscriptionId, MessagingError>
+    where
+        T: DeserializeOwned + Send + 'static,
+        F: Fn(T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), MessagingError>> + Send + 'static,
+    {
+        self.queue_subscribe(topic.name(), group, topic.handler(f))
+            .await
+    }
+}
+
+/// Durable (at-least-once) subscriber backed by a persistent stream.
+///
+/// The consumer named `durable` outlives the process: messages published
+/// while no instance is running are delivered once one comes back.
+/// Instances sharing the same `durable` name compete for messages like a
+/// queue group.
+#[async_trait]
+pub trait DurableSubscriber: Send + Sync {
+    async fn durable_subscribe(
+        &self,
+        topic: &str,
+        durable: &str,
+        handler: Handler,
+    ) -> Result<SubscriptionId, MessagingError>;
 
     async fn unsubscribe(&self, topic: &str) -> Result<(), MessagingError>;
 
     async fn close(&self) -> Result<(), MessagingError>;
 }
 
+#[async_trait]
+pub trait DurableSubscriberExt: DurableSubscriber {
+    async fn durable_subscribe_to<T, F, Fut>(
+        &self,
+        topic: &Topic<T>,
+        durable: &str,
+        f: F,
+    ) -> Result<SubscriptionId, MessagingError>
+    where
+        T: DeserializeOwned + Send + 'static,
+        F: Fn(T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), MessagingError>> + Send + 'static,
+    {
+        self.durable_subscribe(topic.name(), durable, topic.handler(f))
+            .await
+    }
+}
+
 /// Request / reply broker.
 #[async_trait]
 pub trait Broker: Send + Sync {
     /// Send a request to `pattern`, wait up to `timeout`, deserialize reply as `R`.
+    ///
+    /// The request is encoded with the broker's default codec; the reply is
+    /// decoded with the codec named by its `content-type`.
     async fn request<T, R>(
         &self,
         pattern: &str,
@@ -98,6 +236,36 @@ pub trait Broker: Send + Sync {
         T: serde::Serialize + Send + Sync,
         R: serde::de::DeserializeOwned;
 
+    /// Scatter-gather: send one request and collect the replies of every
+    /// responder until `options` says stop.
+    ///
+    /// Each reply is decoded on its own, so one responder's error reply
+    /// does not hide the others'. Fails only if the request cannot be sent
+    /// or nobody listens.
+    async fn request_many<T, R>(
+        &self,
+        pattern: &str,
+        payload: &T,
+        attrs: HashMap<String, String>,
+        options: RequestMany,
+    ) -> Result<Vec<Result<R, MessagingError>>, MessagingError>
+    where
+        T: serde::Serialize + Send + Sync,
+        R: serde::de::DeserializeOwned + Send;
+
+    /// Send a request answered by a `stream_handler` and read the chunks
+    /// as they arrive; each must follow the previous one within `timeout`.
+    async fn request_stream<T, R>(
+        &self,
+        pattern: &str,
+        payload: &T,
+        attrs: HashMap<String, String>,
+        timeout: Duration,
+    ) -> Result<ReplyStream<R>, MessagingError>
+    where
+        T: serde::Serialize + Send + Sync,
+        R: serde::de::DeserializeOwned + Send + 'static;
+
     async fn close(&self) -> Result<(), MessagingError>;
 }
 
@@ -110,3 +278,6 @@ pub trait QueueClient: Publisher + QueueSubscriber {}
 impl<T: Publisher + QueueSubscriber> QueueClient for T {}
 
 impl<T: Publisher + ?Sized> PublisherExt for T {}
+impl<T: Subscriber + ?Sized> SubscriberExt for T {}
+impl<T: QueueSubscriber + ?Sized> QueueSubscriberExt for T {}
+impl<T: DurableSubscriber + ?Sized> DurableSubscriberExt for T {}
@@ -0,0 +1,76 @@
+use once_cell::sync::Lazy;
+
+use crate::meter::get_meter;
+
+pub static RETRY_COUNT: Lazy<opentelemetry::metrics::Counter<u64>> =
+    Lazy::new(|| get_meter().u64_counter("messaging.retries.total").build());
+
+pub static DEAD_LETTER_COUNT: Lazy<opentelemetry::metrics::Counter<u64>> = Lazy::new(|| {
+    get_meter()
+        .u64_counter("messaging.dead_letters.total")
+        .build()
+});
+
+pub static DUPLICATE_COUNT: Lazy<opentelemetry::metrics::Counter<u64>> = Lazy::new(|| {
+    get_meter()
+        .u64_counter("messaging.duplicates.total")
+        .build()
+});
+
+/// Messages received by a subscription and not yet handled (waiting + running).
+pub static PENDING_MESSAGES: Lazy<opentelemetry::metrics::UpDownCounter<i64>> = Lazy::new(|| {
+    get_meter()
+        .i64_up_down_counter("messaging.pending_messages")
+        .build()
+});
+
+/// Handlers currently running.
+pub static ACTIVE_HANDLERS: Lazy<opentelemetry::metrics::UpDownCounter<i64>> = Lazy::new(|| {
+    get_meter()
+        .i64_up_down_counter("messaging.handlers.active")
+        .build()
+});
+
+pub static SLOW_CONSUMER_COUNT: Lazy<opentelemetry::metrics::Counter<u64>> = Lazy::new(|| {
+    get_meter()
+        .u64_counter("messaging.slow_consumers.total")
+        .build()
+});
+
+/// Handlers that panicked (the panic is turned into a handler error).
+pub static PANIC_COUNT: Lazy<opentelemetry::metrics::Counter<u64>> =
+    Lazy::new(|| get_meter().u64_counter("messaging.panics.total").build());
+
+pub static PUBLISHED_COUNT: Lazy<opentelemetry::metrics::Counter<u64>> =
+    Lazy::new(|| get_meter().u64_counter("messaging.published.total").build());
+
+pub static CONSUMED_COUNT: Lazy<opentelemetry::metrics::Counter<u64>> =
+    Lazy::new(|| get_meter().u64_counter("messaging.consumed.total").build());
+
+pub static ERROR_COUNT: Lazy<opentelemetry::metrics::Counter<u64>> =
+    Lazy::new(|| get_meter().u64_counter("messaging.errors.total").build());
+
+/// Publish / handler duration (seconds).
+pub static HANDLER_LATENCY: Lazy<opentelemetry::metrics::Histogram<f64>> = Lazy::new(|| {
+    get_meter()
+        .f64_histogram("messaging.handler.duration")
+        .build()
+});
+
+/// Payload size (bytes).
+pub static PAYLOAD_SIZE: Lazy<opentelemetry::metrics::Histogram<f64>> =
+    Lazy::new(|| get_meter().f64_histogram("messaging.payload.size").build());
+
+/// Time from publish (`start_time` header) to consumption (seconds).
+pub static DELIVERY_LAG: Lazy<opentelemetry::metrics::Histogram<f64>> =
+    Lazy::new(|| get_meter().f64_histogram("messaging.delivery.lag").build());
+
+/// 1 while the client is connected, 0 otherwise.
+pub static CONNECTION_STATE: Lazy<opentelemetry::metrics::Gauge<i64>> =
+    Lazy::new(|| get_meter().i64_gauge("messaging.connection.up").build());
+
+pub static RECONNECT_COUNT: Lazy<opentelemetry::metrics::Counter<u64>> = Lazy::new(|| {
+    get_meter()
+        .u64_counter("messaging.reconnects.total")
+        .build()
+});
@@ -1,4 +1,5 @@
 pub mod http;
+pub mod messaging;
 pub mod system;
 
 use std::{sync::OnceLock, time::Duration};